@@ -0,0 +1,137 @@
+use chip_8::config::{self, Config};
+use chip_8::headless::{self, Options};
+use chip_8::loader;
+use chip_8::renderer::Renderer;
+use chip_8::vm::State;
+use clap::{App, Arg};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let matches = App::new("CHIP-8 headless")
+        .arg(
+            Arg::with_name("PROGRAM")
+                .help("The program to run: a ROM, hex text, .zip or .gz, or - for stdin.")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .long("frames")
+                .value_name("N")
+                .help("The number of 60 Hz frames to run.")
+                .default_value("600"),
+        )
+        .arg(
+            Arg::with_name("until-pc")
+                .long("until-pc")
+                .value_name("ADDRESS")
+                .help("Stop as soon as the PC reaches ADDRESS (hex)."),
+        )
+        .arg(
+            Arg::with_name("keys")
+                .long("keys")
+                .value_name("SCRIPT")
+                .help("Keys to hold down, e.g. `10-20:5,30:a` holds 5 for frames 10 to 20 and A for frame 30."),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("N")
+                .help("Seed for the random number generator.")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("How to write out the final frame buffer.")
+                .possible_values(&["ascii", "hash", "png"])
+                .default_value("ascii"),
+        )
+        .arg(
+            Arg::with_name("png-scale")
+                .long("scale")
+                .value_name("N")
+                .help("Scale PNG output up by N.")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .value_name("FILE")
+                .help("Write the frame buffer to FILE instead of stdout."),
+        )
+        .args(&config::args())
+        .get_matches();
+
+    let rom =
+        loader::load(matches.value_of("PROGRAM").unwrap()).unwrap_or_else(|error| exit(&error));
+
+    // Unlike the other frontends, only a config file given with --config is read, so runs don't
+    // depend on the machine's settings, but the ROM's own and the database's settings are
+    let config = match matches.value_of("config") {
+        Some(path) => Config::load(Some(Path::new(path))).unwrap_or_else(|error| exit(&error)),
+        None => Config::default(),
+    };
+    let settings = config::from_args(&matches).or(&config.rom_settings(&rom));
+    let platform = settings.platform.unwrap_or_default();
+    let options = Options {
+        frames: number(matches.value_of("frames").unwrap()),
+        cycles_per_frame: settings.speed.unwrap_or(10),
+        quirks: settings.quirks(),
+        until_pc: matches
+            .value_of("until-pc")
+            .map(|pc| headless::parse_pc(pc).unwrap_or_else(|error| exit(&error))),
+        keys: headless::parse_keys(matches.value_of("keys").unwrap_or(""))
+            .unwrap_or_else(|error| exit(&error)),
+        seed: number(matches.value_of("seed").unwrap()) as u64,
+        platform,
+        load_address: config::load_address(&matches),
+    };
+    let palette = settings.palette().unwrap_or_else(|error| exit(&error));
+
+    let outcome = headless::run(&rom, &options).unwrap_or_else(|error| exit(&error.to_string()));
+
+    let frame_buffer = &outcome.vm.frame_buffer;
+    let output = match matches.value_of("format") {
+        Some("hash") => format!("{}\n", frame_buffer.hash()).into_bytes(),
+        Some("png") => Renderer::new(palette)
+            .with_pixel_aspect(platform.variant().pixel_aspect())
+            .to_png(frame_buffer, number(matches.value_of("png-scale").unwrap()))
+            .expect("unable to encode PNG"),
+        _ => frame_buffer.to_ascii().into_bytes(),
+    };
+    match matches.value_of("output") {
+        Some(path) => File::create(path)
+            .and_then(|mut file| file.write_all(&output))
+            .expect("unable to write output"),
+        None => io::stdout()
+            .write_all(&output)
+            .expect("unable to write output"),
+    }
+
+    match outcome.state {
+        State::Running => {
+            eprintln!(
+                "stopped after {} frames at {:#05x}",
+                outcome.frames, outcome.vm.pc
+            )
+        }
+        State::Halted(error) | State::Break(error) => {
+            exit(&format!("halted at {:#05x}: {}", outcome.vm.pc, error))
+        }
+    }
+}
+
+fn number(value: &str) -> usize {
+    value
+        .parse()
+        .unwrap_or_else(|_| exit(&format!("invalid number: {}", value)))
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
@@ -0,0 +1,268 @@
+use chip_8::config;
+use chip_8::database;
+use chip_8::keymap::Keymap;
+use chip_8::loader;
+use chip_8::renderer::Palette;
+use chip_8::vm::{Key, State, Vm};
+use clap::{App, Arg};
+use crossterm::cursor::{Hide, MoveTo, MoveToNextLine, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{
+    self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+};
+use crossterm::{execute, queue};
+use std::io::{self, Write};
+use std::process;
+use std::sync::mpsc::{self, Sender};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_micros(16_667);
+
+/// Most terminals only report key presses, so a key counts as held until this long after it was
+/// last pressed or auto-repeated.
+const HOLD: Duration = Duration::from_millis(300);
+
+fn main() {
+    let matches = App::new("CHIP-8 TUI")
+        .arg(
+            Arg::with_name("PROGRAM")
+                .help("The program to run: a ROM, hex text, .zip or .gz, or - for stdin.")
+                .required(true)
+                .index(1),
+        )
+        .args(&config::args())
+        .args(&config::play_args())
+        .get_matches();
+
+    let rom =
+        loader::load(matches.value_of("PROGRAM").unwrap()).unwrap_or_else(|error| exit(&error));
+
+    let settings = config::load_args(&matches, &rom).unwrap_or_else(|error| exit(&error));
+
+    let title = database::title(&config::rom_hash(&rom));
+
+    let palette = settings.palette().unwrap_or_else(|error| exit(&error));
+    let keymap = settings.keymap().unwrap_or_else(|error| exit(&error));
+    let keymap2 = settings.keymap2().unwrap_or_else(|error| exit(&error));
+
+    let (tx_key, rx_key) = mpsc::channel();
+    let (tx_key2, rx_key2) = mpsc::channel();
+    let mut vm = Vm::new(rx_key);
+    vm.connect_keypad2(rx_key2);
+    vm.cycles_per_frame = settings.speed.unwrap_or(10);
+    vm.quirks = settings.quirks();
+    vm.set_platform(settings.platform.unwrap_or_default());
+    if let Some(address) = config::load_address(&matches) {
+        vm.load_address = address;
+    }
+    vm.load_rom(&rom)
+        .unwrap_or_else(|error| exit(&error.to_string()));
+
+    let audio = settings.audio.unwrap_or(true);
+    let keypads = [Keypad::new(keymap, tx_key), Keypad::new(keymap2, tx_key2)];
+    if let Err(error) = run(vm, keypads, &title, palette, audio) {
+        exit(&error.to_string());
+    }
+}
+
+fn run(
+    mut vm: Vm,
+    mut keypads: [Keypad; 2],
+    title: &str,
+    palette: Palette,
+    audio: bool,
+) -> io::Result<()> {
+    let terminal = Terminal::new()?;
+    let mut out = io::stdout();
+    execute!(out, SetTitle(title))?;
+
+    let mut drawn = None;
+    let mut stopped = None;
+    loop {
+        let frame_start = Instant::now();
+
+        while event::poll(Duration::from_secs(0))? {
+            match event::read()? {
+                Event::Key(event) => match event.code {
+                    KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    code => {
+                        if let Some(name) = key_name(code) {
+                            for keypad in keypads.iter_mut() {
+                                keypad.handle(&name, event.kind);
+                            }
+                        }
+                    }
+                },
+                Event::Resize(_, _) => drawn = None,
+                _ => {}
+            }
+        }
+        for keypad in keypads.iter_mut() {
+            if !terminal.key_releases {
+                keypad.expire();
+            }
+            keypad.send();
+        }
+
+        if stopped.is_none() {
+            let sound = vm.sound();
+            let state = vm.frame();
+            // Ring the terminal bell when the buzzer starts
+            if audio && vm.sound() && !sound {
+                execute!(out, Print('\x07'))?;
+            }
+            if let State::Halted(error) | State::Break(error) = state {
+                stopped = Some(format!("halted at {:#05x}: {}", vm.pc, error));
+                drawn = None;
+            }
+        }
+
+        if drawn.as_ref() != Some(&vm.frame_buffer) {
+            if drawn.is_none() {
+                queue!(out, Clear(ClearType::All))?;
+            }
+            queue!(
+                out,
+                MoveTo(0, 0),
+                SetForegroundColor(colour(palette.0[1])),
+                SetBackgroundColor(colour(palette.0[0]))
+            )?;
+            for line in vm.frame_buffer.to_half_blocks() {
+                queue!(out, Print(line), MoveToNextLine(1))?;
+            }
+            queue!(out, ResetColor)?;
+            if let Some(message) = stopped.as_ref() {
+                queue!(out, Print(message), Print(" (Esc to quit)"))?;
+            }
+            out.flush()?;
+            drawn = Some(vm.frame_buffer.clone());
+        }
+
+        sleep(FRAME.checked_sub(frame_start.elapsed()).unwrap_or_default());
+    }
+}
+
+/// A keypad played from the keyboard, sending the key held down to the VM.
+struct Keypad {
+    keymap: Keymap,
+    tx_key: Sender<Option<Key>>,
+    /// The key held down and when it was last pressed.
+    held: Option<(Key, Instant)>,
+    sent: Option<Key>,
+}
+
+impl Keypad {
+    fn new(keymap: Keymap, tx_key: Sender<Option<Key>>) -> Self {
+        Self {
+            keymap,
+            tx_key,
+            held: None,
+            sent: None,
+        }
+    }
+
+    /// Presses or releases the key bound to the host key called `name`, if there is one.
+    fn handle(&mut self, name: &str, kind: KeyEventKind) {
+        if let Some(key) = self.keymap.get(name) {
+            if kind == KeyEventKind::Release {
+                if self.held.map(|(held, _)| held) == Some(key) {
+                    self.held = None;
+                }
+            } else {
+                self.held = Some((key, Instant::now()));
+            }
+        }
+    }
+
+    /// Releases the key if it hasn't been pressed or auto-repeated for `HOLD`.
+    fn expire(&mut self) {
+        self.held = self.held.filter(|(_, pressed)| pressed.elapsed() < HOLD);
+    }
+
+    /// Sends the key held down, if it changed.
+    fn send(&mut self) {
+        let key = self.held.map(|(key, _)| key);
+        if key != self.sent {
+            self.tx_key.send(key).expect("key send failed");
+            self.sent = key;
+        }
+    }
+}
+
+/// Puts the terminal into raw mode on an alternate screen, and restores it when dropped.
+struct Terminal {
+    /// Whether the terminal reports key releases, rather than just presses.
+    key_releases: bool,
+}
+
+impl Terminal {
+    fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+
+        let key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if key_releases {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Self { key_releases })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.key_releases {
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn exit(error: &str) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+fn colour(rgb: u32) -> Color {
+    let [_, r, g, b] = rgb.to_be_bytes();
+    Color::Rgb { r, g, b }
+}
+
+/// The keymap name of a terminal key, matching the names used by the window frontend.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char('\'') => "apostrophe".to_string(),
+        KeyCode::Char('`') => "backquote".to_string(),
+        KeyCode::Char('\\') => "backslash".to_string(),
+        KeyCode::Char(',') => "comma".to_string(),
+        KeyCode::Char('=') => "equal".to_string(),
+        KeyCode::Char('[') => "leftbracket".to_string(),
+        KeyCode::Char('-') => "minus".to_string(),
+        KeyCode::Char('.') => "period".to_string(),
+        KeyCode::Char(']') => "rightbracket".to_string(),
+        KeyCode::Char(';') => "semicolon".to_string(),
+        KeyCode::Char('/') => "slash".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        _ => return None,
+    };
+    Some(name)
+}
@@ -0,0 +1,452 @@
+//! Settings read from `~/.config/chip-8/config.toml`, e.g.
+//!
+//! ```toml
+//! # Defaults for every ROM
+//! platform = "chip-8"
+//! speed = 10
+//! palette = "amber"
+//! scale = 8
+//! audio = true
+//!
+//! [quirks]
+//! display_wait = false
+//!
+//! # Bind host keys to keypad keys, on top of the default layout unless it's turned off
+//! default_keys = true
+//! [keys]
+//! a = "4"
+//! z = "5"
+//!
+//! # Overrides for a single ROM, keyed by its SHA-1
+//! [rom.0123456789abcdef0123456789abcdef01234567]
+//! speed = 15
+//! quirks.display_wait = true
+//!
+//! # And to keys on CHIP-8X's second keypad
+//! [keys2]
+//! numpad5 = "5"
+//!
+//! [rom.0123456789abcdef0123456789abcdef01234567.keys]
+//! up = "2"
+//! down = "8"
+//! ```
+
+use crate::database;
+use crate::keymap::{self, Keymap};
+use crate::loader;
+use crate::renderer::Palette;
+use crate::vm::{Platform, Quirks, PLATFORMS};
+use clap::{Arg, ArgMatches};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Settings that can be given globally or for a single ROM. Anything left out falls back to the
+/// global setting and then to the emulator's default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub platform: Option<Platform>,
+    /// The number of instructions executed per 60 Hz frame.
+    pub speed: Option<usize>,
+    #[serde(default)]
+    pub quirks: QuirkSettings,
+    /// A palette name or hex colours, as for `Palette::from_str`.
+    pub palette: Option<String>,
+    /// How many times bigger than the display the window is.
+    pub scale: Option<usize>,
+    /// Host key names bound to keypad keys given as hex digits.
+    #[serde(default)]
+    pub keys: HashMap<String, String>,
+    /// Host key names bound to keys on CHIP-8X's second keypad.
+    #[serde(default)]
+    pub keys2: HashMap<String, String>,
+    /// Whether `keys` and `keys2` are bound on top of the default layouts rather than replacing
+    /// them.
+    pub default_keys: Option<bool>,
+    /// Whether to ring the terminal bell when the buzzer starts.
+    pub audio: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuirkSettings {
+    pub display_wait: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Settings,
+    /// Settings for individual ROMs, keyed by the SHA-1 of the ROM.
+    #[serde(default)]
+    pub rom: HashMap<String, Settings>,
+}
+
+impl Settings {
+    /// Fills in anything not set here from `defaults`.
+    pub fn or(mut self, defaults: &Settings) -> Self {
+        let mut keys = defaults.keys.clone();
+        keys.extend(self.keys);
+        self.keys = keys;
+        let mut keys2 = defaults.keys2.clone();
+        keys2.extend(self.keys2);
+        self.keys2 = keys2;
+
+        self.platform = self.platform.or(defaults.platform);
+        self.speed = self.speed.or(defaults.speed);
+        self.quirks.display_wait = self.quirks.display_wait.or(defaults.quirks.display_wait);
+        self.default_keys = self.default_keys.or(defaults.default_keys);
+        self.palette = self.palette.or_else(|| defaults.palette.clone());
+        self.scale = self.scale.or(defaults.scale);
+        self.audio = self.audio.or(defaults.audio);
+        self
+    }
+
+    /// The quirks set here, falling back to those the platform's programs expect.
+    pub fn quirks(&self) -> Quirks {
+        let defaults = self.platform.unwrap_or_default().variant().quirks();
+        Quirks {
+            display_wait: self.quirks.display_wait.unwrap_or(defaults.display_wait),
+        }
+    }
+
+    pub fn palette(&self) -> Result<Palette, String> {
+        self.palette
+            .as_deref()
+            .map_or(Ok(Palette::default()), str::parse)
+    }
+
+    /// The default layout with these bindings applied, or only these bindings if the default
+    /// layout is turned off.
+    pub fn keymap(&self) -> Result<Keymap, String> {
+        self.bind(Keymap::default(), &self.keys)
+    }
+
+    /// The keymap of CHIP-8X's second keypad, as for `keymap`.
+    pub fn keymap2(&self) -> Result<Keymap, String> {
+        self.bind(Keymap::second_keypad(), &self.keys2)
+    }
+
+    fn bind(&self, default: Keymap, keys: &HashMap<String, String>) -> Result<Keymap, String> {
+        let mut keymap = if self.default_keys.unwrap_or(true) {
+            default
+        } else {
+            Keymap::empty()
+        };
+        keymap.bind_all(keys.iter().map(|(name, key)| (name.as_str(), key.as_str())))?;
+        Ok(keymap)
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/chip-8/config.toml`, or `~/.config/chip-8/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("chip-8").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, or at `Config::path` if there is one. Only an explicitly
+    /// given file has to exist.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        match fs::read_to_string(&path) {
+            Ok(config) => {
+                Self::parse(&config).map_err(|error| format!("{}: {}", path.display(), error))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {
+                Ok(Self::default())
+            }
+            Err(error) => Err(format!("{}: {}", path.display(), error)),
+        }
+    }
+
+    pub fn parse(config: &str) -> Result<Self, String> {
+        toml::from_str(config).map_err(|error| error.to_string())
+    }
+
+    /// The settings for `rom` from its `[rom.<sha1>]` section, falling back to those recommended
+    /// by the ROM database and then the global ones.
+    pub fn rom_settings(&self, rom: &[u8]) -> Settings {
+        let rom_hash = rom_hash(rom);
+        let settings = self.rom.get(&rom_hash).cloned().unwrap_or_default();
+        let settings = match database::lookup(&rom_hash) {
+            Some(entry) => settings.or(&entry.settings()),
+            None => settings,
+        };
+        settings.or(&self.defaults)
+    }
+
+    /// The settings for the ROM with SHA-1 `rom_hash`, falling back to the global ones.
+    pub fn settings(&self, rom_hash: &str) -> Settings {
+        match self.rom.get(rom_hash) {
+            Some(rom) => rom.clone().or(&self.defaults),
+            None => self.defaults.clone(),
+        }
+    }
+}
+
+/// The settings for `rom` from the config file at `path`, or the default location, as for
+/// `Config::rom_settings`.
+pub fn load(path: Option<&Path>, rom: &[u8]) -> Result<Settings, String> {
+    Ok(Config::load(path)?.rom_settings(rom))
+}
+
+/// The command line options every frontend takes, which override the config file.
+pub fn args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("config")
+            .long("config")
+            .value_name("FILE")
+            .help("Read settings from FILE instead of ~/.config/chip-8/config.toml."),
+        Arg::with_name("platform")
+            .long("platform")
+            .value_name("PLATFORM")
+            .help("The interpreter variant to emulate [default: chip-8].")
+            .possible_values(&PLATFORMS.map(|(name, _)| name)),
+        Arg::with_name("speed")
+            .long("speed")
+            .value_name("N")
+            .help("The number of instructions executed per frame [default: 10].")
+            .validator(|speed| speed.parse::<usize>().map(|_| ()).map_err(|e| e.to_string())),
+        Arg::with_name("palette")
+            .long("palette")
+            .value_name("PALETTE")
+            .help("default, green, amber, lcd, high-contrast or 2 or 4 hex colours, e.g. 000000,ffffff [default: default].")
+            .validator(|palette| palette.parse::<Palette>().map(|_| ())),
+        Arg::with_name("display-wait")
+            .long("display-wait")
+            .help("Make DRW wait for the next frame, as on the COSMAC VIP."),
+        Arg::with_name("no-display-wait")
+            .long("no-display-wait")
+            .help("Let DRW draw straight away, even if the platform or config file says otherwise.")
+            .conflicts_with("display-wait"),
+        Arg::with_name("load-address")
+            .long("load-address")
+            .value_name("ADDRESS")
+            .help("Load the program at ADDRESS (hex) instead of the platform's address.")
+            .validator(|address| loader::parse_address(&address).map(|_| ())),
+    ]
+}
+
+/// The command line options of the frontends that are played, for binding keys and the bell.
+pub fn play_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("bindings")
+            .long("keys")
+            .value_name("BINDINGS")
+            .help("Extra key bindings as HOST=KEYPAD pairs, e.g. up=2,left=4,right=6,down=8.")
+            .validator(|keys| Keymap::empty().bind_str(&keys)),
+        Arg::with_name("bindings2")
+            .long("keys2")
+            .value_name("BINDINGS")
+            .help("Extra key bindings for CHIP-8X's second keypad, as for --keys.")
+            .validator(|keys| Keymap::empty().bind_str(&keys)),
+        Arg::with_name("no-default-keys")
+            .long("no-default-keys")
+            .help("Only use the bindings from --keys and the config file, not the QWERTY layout."),
+        Arg::with_name("mute")
+            .long("mute")
+            .help("Don't ring the terminal bell when the buzzer starts."),
+    ]
+}
+
+/// The settings given on the command line with `args`, `play_args` and the window's `--scale`.
+pub fn from_args(matches: &ArgMatches) -> Settings {
+    let bindings = |name| {
+        matches
+            .value_of(name)
+            .map(|keys| keymap::parse_bindings(keys).unwrap())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(host, key)| (host.to_string(), key.to_string()))
+            .collect()
+    };
+    // Flags turning a setting off
+    let off = |name| Some(false).filter(|_| matches.is_present(name));
+    Settings {
+        platform: matches
+            .value_of("platform")
+            .map(|platform| platform.parse().unwrap()),
+        speed: matches
+            .value_of("speed")
+            .map(|speed| speed.parse().unwrap()),
+        quirks: QuirkSettings {
+            display_wait: Some(true)
+                .filter(|_| matches.is_present("display-wait"))
+                .or_else(|| off("no-display-wait")),
+        },
+        palette: matches.value_of("palette").map(str::to_string),
+        scale: matches
+            .value_of("scale")
+            .map(|scale| scale.parse().unwrap()),
+        keys: bindings("bindings"),
+        keys2: bindings("bindings2"),
+        default_keys: off("no-default-keys"),
+        audio: off("mute"),
+    }
+}
+
+/// The load address given with `--load-address`, if any.
+pub fn load_address(matches: &ArgMatches) -> Option<u16> {
+    matches
+        .value_of("load-address")
+        .map(|address| loader::parse_address(address).unwrap())
+}
+
+/// The settings for `rom` from the command line, falling back to those from `load` with the
+/// config file given by `--config`.
+pub fn load_args(matches: &ArgMatches, rom: &[u8]) -> Result<Settings, String> {
+    let settings = load(matches.value_of("config").map(Path::new), rom)?;
+    Ok(from_args(matches).or(&settings))
+}
+
+/// The SHA-1 of a ROM as lowercase hex, used to look up per-ROM settings.
+pub fn rom_hash(rom: &[u8]) -> String {
+    Sha1::digest(rom)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Key;
+
+    #[test]
+    fn test_settings() {
+        let config = Config::parse(
+            r#"
+            speed = 12
+            palette = "amber"
+            platform = "eti-660"
+
+            [keys]
+            a = "4"
+
+            [rom.abc]
+            speed = 20
+            quirks.display_wait = true
+
+            [rom.abc.keys]
+            up = "2"
+            a = "6"
+            "#,
+        )
+        .unwrap();
+
+        let settings = config.settings("def");
+        assert_eq!(settings.speed, Some(12));
+        assert!(!settings.quirks().display_wait);
+        let keymap = settings.keymap().unwrap();
+        assert_eq!(keymap.get("a"), Some(Key::Key4));
+        assert_eq!(keymap.get("up"), None);
+
+        let settings = config.settings("abc");
+        assert_eq!(settings.platform, Some(Platform::Eti660));
+        assert_eq!(settings.speed, Some(20));
+        assert_eq!(settings.palette(), "amber".parse());
+        assert!(settings.quirks().display_wait);
+        let keymap = settings.keymap().unwrap();
+        assert_eq!(keymap.get("a"), Some(Key::Key6));
+        assert_eq!(keymap.get("up"), Some(Key::Key2));
+
+        let config = Config::parse("default_keys = false\n[keys]\nk = \"5\"").unwrap();
+        let keymap = config.settings("").keymap().unwrap();
+        assert_eq!(keymap.get("k"), Some(Key::Key5));
+        assert_eq!(keymap.get("q"), None);
+        assert_eq!(config.settings("").keymap2().unwrap().get("k"), None);
+
+        let config = Config::parse("[keys2]\nnumpad5 = \"5\"").unwrap();
+        let keymap2 = config.settings("").keymap2().unwrap();
+        assert_eq!(keymap2.get("numpad5"), Some(Key::Key5));
+        assert_eq!(keymap2.get("k"), Some(Key::Key8));
+
+        // CHIP-8X programs wait for the display unless told not to
+        let config = Config::parse("platform = \"chip-8x\"").unwrap();
+        assert!(config.settings("").quirks().display_wait);
+        let config = Config::parse("platform = \"chip-8x\"\nquirks.display_wait = false").unwrap();
+        assert!(!config.settings("").quirks().display_wait);
+
+        let config = Config::parse("[keys]\na = \"x\"").unwrap();
+        assert!(config.settings("").keymap().is_err());
+        let config = Config::parse("[keys]\nspce = \"5\"").unwrap();
+        assert!(config.settings("").keymap().is_err());
+        let config = Config::parse("palette = \"purple\"").unwrap();
+        assert!(config.settings("").palette().is_err());
+        assert!(Config::parse("sped = 1").is_err());
+        assert!(Config::parse("[rom.abc]\nsped = 1").is_err());
+    }
+
+    #[test]
+    fn test_rom_settings() {
+        let draw = include_bytes!("../tests/roms/draw.ch8");
+        let config = Config::parse(&format!(
+            r#"
+            speed = 12
+            palette = "amber"
+
+            [rom.{}]
+            speed = 20
+            "#,
+            rom_hash(draw)
+        ))
+        .unwrap();
+
+        // The database's colours win over the global palette, but not over the ROM's section
+        let settings = config.rom_settings(draw);
+        assert_eq!(settings.speed, Some(20));
+        assert_eq!(settings.palette.as_deref(), Some("000000,ffffff"));
+        assert_eq!(
+            config.rom_settings(&[0x12, 0x00]).palette.as_deref(),
+            Some("amber")
+        );
+    }
+
+    #[test]
+    fn test_from_args() {
+        let matches = clap::App::new("test")
+            .args(&args())
+            .args(&play_args())
+            .get_matches_from(vec![
+                "test",
+                "--speed",
+                "20",
+                "--no-display-wait",
+                "--keys",
+                "up=2, left=4",
+                "--mute",
+            ]);
+        let settings = from_args(&matches);
+        assert_eq!(settings.speed, Some(20));
+        assert_eq!(settings.quirks.display_wait, Some(false));
+        assert_eq!(settings.audio, Some(false));
+        assert_eq!(settings.default_keys, None);
+        assert_eq!(settings.keymap().unwrap().get("left"), Some(Key::Key4));
+        assert_eq!(settings.keymap().unwrap().get("q"), Some(Key::Key4));
+
+        let config = Config::parse("speed = 12\naudio = true").unwrap();
+        let settings = settings.or(&config.settings(""));
+        assert_eq!(settings.speed, Some(20));
+        assert_eq!(settings.audio, Some(false));
+    }
+
+    #[test]
+    fn test_rom_hash() {
+        assert_eq!(rom_hash(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+}
@@ -0,0 +1,138 @@
+//! Settings for known ROMs, compiled in from `database.json` and keyed by the ROM's SHA-1.
+//!
+//! Entries follow the shape of the community chip-8-database, with keys given as host key names
+//! bound to keypad keys like the config file:
+//!
+//! ```json
+//! "<sha1>": {
+//!   "title": "...",
+//!   "author": "...",
+//!   "platform": "chip-8",
+//!   "tick_rate": 15,
+//!   "quirks": { "display_wait": true },
+//!   "keys": { "up": "2", "down": "8" },
+//!   "colours": ["000000", "ffffff"]
+//! }
+//! ```
+
+use crate::config::{QuirkSettings, Settings};
+use crate::vm;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const DATABASE: &str = include_str!("database.json");
+
+/// The platform a ROM was written for, which may not be one that can be emulated.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum RomPlatform {
+    Supported(vm::Platform),
+    /// Any other platform by its name in the database, e.g. `schip` or `xo-chip`.
+    Unsupported(String),
+}
+
+impl RomPlatform {
+    /// The platform to run the ROM on, if it can be emulated.
+    pub fn supported(&self) -> Option<vm::Platform> {
+        match self {
+            RomPlatform::Supported(platform) => Some(*platform),
+            RomPlatform::Unsupported(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub title: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub platform: RomPlatform,
+    /// The number of instructions executed per frame.
+    pub tick_rate: Option<usize>,
+    #[serde(default)]
+    pub quirks: QuirkSettings,
+    #[serde(default)]
+    pub keys: HashMap<String, String>,
+    /// Hex colours, background first, as for `Palette::from_str`.
+    pub colours: Option<Vec<String>>,
+}
+
+impl Entry {
+    /// The recommended settings, for use under the ROM's config section and over the global ones.
+    pub fn settings(&self) -> Settings {
+        Settings {
+            platform: self.platform.supported(),
+            speed: self.tick_rate,
+            quirks: self.quirks,
+            palette: self.colours.as_ref().map(|colours| colours.join(",")),
+            keys: self.keys.clone(),
+            ..Settings::default()
+        }
+    }
+}
+
+/// The entry for the ROM with SHA-1 `rom_hash`, if it's a known ROM.
+pub fn lookup(rom_hash: &str) -> Option<&'static Entry> {
+    static ENTRIES: OnceLock<HashMap<String, Entry>> = OnceLock::new();
+    ENTRIES
+        .get_or_init(|| serde_json::from_str(DATABASE).expect("invalid ROM database"))
+        .get(rom_hash)
+}
+
+/// The window title for a ROM: its title if it's a known ROM. Warns on stderr if it was written
+/// for a platform that can't be emulated.
+pub fn title(rom_hash: &str) -> String {
+    match lookup(rom_hash) {
+        Some(entry) => {
+            if let RomPlatform::Unsupported(name) = &entry.platform {
+                eprintln!(
+                    "{} is a {} ROM, which may not run correctly",
+                    entry.title, name
+                );
+            }
+            format!("CHIP-8 - {}", entry.title)
+        }
+        None => "CHIP-8".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::rom_hash;
+
+    #[test]
+    fn test_lookup() {
+        let rom = include_bytes!("../tests/roms/draw.ch8");
+        let entry = lookup(&rom_hash(rom)).unwrap();
+        assert_eq!(entry.title, "Draw test");
+        assert_eq!(entry.platform, RomPlatform::Supported(vm::Platform::Chip8));
+        assert!(entry.settings().palette().is_ok());
+
+        assert_eq!(lookup(&rom_hash(&[])), None);
+        assert_eq!(title(&rom_hash(rom)), "CHIP-8 - Draw test");
+        assert_eq!(title(&rom_hash(&[])), "CHIP-8");
+    }
+
+    #[test]
+    fn test_platform() {
+        let entry = |platform| {
+            serde_json::from_str::<Entry>(&format!(
+                r#"{{"title": "Test", "platform": "{}"}}"#,
+                platform
+            ))
+            .unwrap()
+        };
+        assert_eq!(
+            entry("chip-8x").settings().platform,
+            Some(vm::Platform::Chip8X)
+        );
+        assert_eq!(
+            entry("xo-chip").platform,
+            RomPlatform::Unsupported("xo-chip".to_string())
+        );
+        assert_eq!(entry("schip").settings().platform, None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Runs ROMs without opening a window, e.g. for automated tests on machines without a display.
+
+use crate::vm::{Key, LoadError, Platform, Quirks, State, Vm};
+use std::sync::mpsc;
+
+/// Holds `key` down from frame `from` up to and including frame `to`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyPress {
+    pub from: usize,
+    pub to: usize,
+    pub key: Key,
+}
+
+pub struct Options {
+    pub frames: usize,
+    pub cycles_per_frame: usize,
+    pub quirks: Quirks,
+    /// Stop as soon as the PC reaches this address.
+    pub until_pc: Option<u16>,
+    pub keys: Vec<KeyPress>,
+    pub seed: u64,
+    pub platform: Platform,
+    /// Load the ROM here instead of at the platform's load address.
+    pub load_address: Option<u16>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            frames: 600,
+            cycles_per_frame: 10,
+            quirks: Quirks::default(),
+            until_pc: None,
+            keys: Vec::new(),
+            seed: 0,
+            platform: Platform::Chip8,
+            load_address: None,
+        }
+    }
+}
+
+pub struct Outcome {
+    pub vm: Vm,
+    /// The number of frames run, including the one that was stopped early.
+    pub frames: usize,
+    pub state: State,
+}
+
+pub fn run(rom: &[u8], options: &Options) -> Result<Outcome, LoadError> {
+    let (tx_key, rx_key) = mpsc::channel();
+    let mut vm = Vm::new(rx_key);
+    vm.cycles_per_frame = options.cycles_per_frame;
+    vm.quirks = options.quirks;
+    vm.seed(options.seed);
+    vm.set_platform(options.platform);
+    if let Some(load_address) = options.load_address {
+        vm.load_address = load_address;
+    }
+    vm.load_rom(rom)?;
+
+    let mut state = State::Running;
+    for frame in 0..options.frames {
+        let key = options
+            .keys
+            .iter()
+            .find(|press| (press.from..=press.to).contains(&frame))
+            .map(|press| press.key);
+        tx_key.send(key).expect("key send failed");
+
+        let end = vm.frames() + 1;
+        while vm.frames() < end {
+            state = vm.step();
+            if state != State::Running || options.until_pc == Some(vm.pc) {
+                return Ok(Outcome {
+                    vm,
+                    frames: frame + 1,
+                    state,
+                });
+            }
+        }
+    }
+
+    Ok(Outcome {
+        vm,
+        frames: options.frames,
+        state,
+    })
+}
+
+/// Parses a hex address such as `0x22c` or `22c`, e.g. for `--until-pc`.
+pub fn parse_pc(pc: &str) -> Result<u16, String> {
+    u16::from_str_radix(pc.trim_start_matches("0x"), 16).map_err(|_| format!("invalid PC: {}", pc))
+}
+
+/// Parses a comma separated list of `FRAME:KEY` or `FROM-TO:KEY`, e.g. `10-20:5,30:a`.
+pub fn parse_keys(script: &str) -> Result<Vec<KeyPress>, String> {
+    script
+        .split(',')
+        .filter(|press| !press.trim().is_empty())
+        .map(|press| {
+            let invalid = || format!("invalid key press: {:?}", press);
+            let mut parts = press.trim().splitn(2, ':');
+            let frames = parts.next().ok_or_else(invalid)?;
+            let key = parts.next().ok_or_else(invalid)?;
+
+            let mut frames = frames.splitn(2, '-').map(str::parse::<usize>);
+            let from = frames.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+            let to = match frames.next() {
+                Some(to) => to.map_err(|_| invalid())?,
+                None => from,
+            };
+            let key = key.trim().parse().map_err(|_| invalid())?;
+
+            Ok(KeyPress { from, to, key })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        let keys = parse_keys("10-20:5, 30:a").unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                KeyPress {
+                    from: 10,
+                    to: 20,
+                    key: Key::Key5
+                },
+                KeyPress {
+                    from: 30,
+                    to: 30,
+                    key: Key::KeyA
+                },
+            ]
+        );
+
+        assert_eq!(parse_keys("").unwrap(), vec![]);
+        assert!(parse_keys("10").is_err());
+        assert!(parse_keys("10:g").is_err());
+        assert!(parse_keys("10:10").is_err());
+        assert!(parse_keys("x-20:1").is_err());
+    }
+
+    #[test]
+    fn test_parse_pc() {
+        assert_eq!(parse_pc("0x22c"), Ok(0x22c));
+        assert_eq!(parse_pc("100"), Ok(0x100));
+        assert_eq!(parse_pc("0x"), Err("invalid PC: 0x".to_string()));
+        assert!(parse_pc("10000").is_err());
+    }
+
+    #[test]
+    fn test_run_until_pc() {
+        let options = Options {
+            until_pc: Some(0x22c),
+            ..Options::default()
+        };
+        let outcome = run(include_bytes!("../tests/roms/draw.ch8"), &options).unwrap();
+
+        assert_eq!(outcome.state, State::Running);
+        assert_eq!(outcome.vm.pc, 0x22c);
+        assert_eq!(outcome.frames, 15);
+        assert!(outcome.vm.frame_buffer.pixel(62, 0));
+    }
+}
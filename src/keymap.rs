@@ -0,0 +1,211 @@
+//! Maps keys on the host keyboard to the CHIP-8 hex keypad.
+//!
+//! Host keys are named the way minifb names them, case-insensitively and with or without the
+//! `Key` prefix on digits, e.g. `1`, `q`, `up`, `space` or `numpad2`. `HOST_KEYS` lists them all.
+
+use crate::vm::Key;
+use std::collections::HashMap;
+
+/// The host keys that can be bound, by name, in the same order as the frontends list them.
+/// Function keys, Tab and Page Up and Down are left out as they're the window's hotkeys.
+#[rustfmt::skip]
+pub const HOST_KEYS: [&str; 64] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m",
+    "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z",
+    "up", "down", "left", "right", "space", "enter", "backspace",
+    "apostrophe", "backquote", "backslash", "comma", "equal", "leftbracket", "minus",
+    "period", "rightbracket", "semicolon", "slash",
+    "numpad0", "numpad1", "numpad2", "numpad3", "numpad4",
+    "numpad5", "numpad6", "numpad7", "numpad8", "numpad9",
+];
+
+/// The default layout, for QWERTY keyboards:
+///
+/// ```text
+/// +---+---+---+---+    +---+---+---+---+
+/// | 1 | 2 | 3 | 4 |    | 1 | 2 | 3 | C |
+/// +---+---+---+---+    +---+---+---+---+
+/// | Q | W | E | R |    | 4 | 5 | 6 | D |
+/// +---+---+---+---+ => +---+---+---+---+
+/// | A | S | D | F |    | 7 | 8 | 9 | E |
+/// +---+---+---+---+    +---+---+---+---+
+/// | Z | X | C | V |    | A | 0 | B | F |
+/// +---+---+---+---+    +---+---+---+---+
+/// ```
+const DEFAULT: [(&str, Key); 16] = [
+    ("1", Key::Key1),
+    ("2", Key::Key2),
+    ("3", Key::Key3),
+    ("4", Key::KeyC),
+    ("q", Key::Key4),
+    ("w", Key::Key5),
+    ("e", Key::Key6),
+    ("r", Key::KeyD),
+    ("a", Key::Key7),
+    ("s", Key::Key8),
+    ("d", Key::Key9),
+    ("f", Key::KeyE),
+    ("z", Key::KeyA),
+    ("x", Key::Key0),
+    ("c", Key::KeyB),
+    ("v", Key::KeyF),
+];
+
+/// The default layout of CHIP-8X's second keypad, on the right of a QWERTY keyboard:
+///
+/// ```text
+/// +---+---+---+---+    +---+---+---+---+
+/// | 7 | 8 | 9 | 0 |    | 1 | 2 | 3 | C |
+/// +---+---+---+---+    +---+---+---+---+
+/// | U | I | O | P |    | 4 | 5 | 6 | D |
+/// +---+---+---+---+ => +---+---+---+---+
+/// | J | K | L | ; |    | 7 | 8 | 9 | E |
+/// +---+---+---+---+    +---+---+---+---+
+/// | M | , | . | / |    | A | 0 | B | F |
+/// +---+---+---+---+    +---+---+---+---+
+/// ```
+const DEFAULT2: [(&str, Key); 16] = [
+    ("7", Key::Key1),
+    ("8", Key::Key2),
+    ("9", Key::Key3),
+    ("0", Key::KeyC),
+    ("u", Key::Key4),
+    ("i", Key::Key5),
+    ("o", Key::Key6),
+    ("p", Key::KeyD),
+    ("j", Key::Key7),
+    ("k", Key::Key8),
+    ("l", Key::Key9),
+    ("semicolon", Key::KeyE),
+    ("m", Key::KeyA),
+    ("comma", Key::Key0),
+    ("period", Key::KeyB),
+    ("slash", Key::KeyF),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: HashMap<String, Key>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT
+                .iter()
+                .map(|(name, key)| (name.to_string(), *key))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// The default layout for CHIP-8X's second keypad.
+    pub fn second_keypad() -> Self {
+        Self {
+            bindings: DEFAULT2
+                .iter()
+                .map(|(name, key)| (name.to_string(), *key))
+                .collect(),
+        }
+    }
+
+    /// A keymap with nothing bound, to build a layout from scratch.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// The keypad key bound to the host key called `name`.
+    pub fn get(&self, name: &str) -> Option<Key> {
+        self.bindings.get(&normalize(name)).copied()
+    }
+
+    /// Binds host key `name` to `key`, replacing any existing binding for `name`. Names that
+    /// aren't in `HOST_KEYS` are refused, so typos don't silently bind nothing.
+    pub fn bind(&mut self, name: &str, key: Key) -> Result<(), String> {
+        let name = normalize(name);
+        if !HOST_KEYS.contains(&name.as_str()) {
+            return Err(format!("unknown host key: {:?}", name));
+        }
+        self.bindings.insert(name, key);
+        Ok(())
+    }
+
+    /// Binds every `(host key, keypad key)` pair, where the keypad key is a hex digit.
+    pub fn bind_all<'a>(
+        &mut self,
+        bindings: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<(), String> {
+        for (name, key) in bindings {
+            self.bind(name, key.trim().parse()?)?;
+        }
+        Ok(())
+    }
+
+    /// Binds a comma separated list of `HOST=KEYPAD` pairs, e.g. `up=2,left=4`.
+    pub fn bind_str(&mut self, bindings: &str) -> Result<(), String> {
+        self.bind_all(parse_bindings(bindings)?)
+    }
+}
+
+/// Splits a comma separated list of `HOST=KEYPAD` pairs into its pairs, without checking them.
+pub fn parse_bindings(bindings: &str) -> Result<Vec<(&str, &str)>, String> {
+    bindings
+        .split(',')
+        .filter(|binding| !binding.trim().is_empty())
+        .map(|binding| {
+            let mut parts = binding.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(key)) => Ok((name.trim(), key.trim())),
+                _ => Err(format!("invalid key binding: {:?}", binding)),
+            }
+        })
+        .collect()
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.strip_prefix("key") {
+        Some(digit) if digit.len() == 1 && digit.chars().all(|c| c.is_ascii_digit()) => {
+            digit.to_string()
+        }
+        _ if name == " " => "space".to_string(),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keymap() {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.get("Key4"), Some(Key::KeyC));
+        assert_eq!(keymap.get("Q"), Some(Key::Key4));
+        assert_eq!(keymap.get("Up"), None);
+
+        keymap.bind_str("Up=2, q=7,Key4=f").unwrap();
+        assert_eq!(keymap.get("Up"), Some(Key::Key2));
+        assert_eq!(keymap.get("q"), Some(Key::Key7));
+        assert_eq!(keymap.get("4"), Some(Key::KeyF));
+        assert_eq!(keymap.get("W"), Some(Key::Key5));
+
+        let keymap = Keymap::second_keypad();
+        assert_eq!(keymap.get("semicolon"), Some(Key::KeyE));
+        assert_eq!(keymap.get("q"), None);
+
+        let mut keymap = Keymap::empty();
+        keymap.bind_str("a=7").unwrap();
+        assert_eq!(keymap.get("a"), Some(Key::Key7));
+        assert_eq!(keymap.get("1"), None);
+
+        assert!(keymap.bind_str("up").is_err());
+        assert!(keymap.bind_str("uo=2").is_err());
+        assert!(keymap.bind_str("tab=2").is_err());
+        assert!(keymap.bind_str("up=10").is_err());
+    }
+}
@@ -0,0 +1,11 @@
+pub mod config;
+pub mod database;
+pub mod headless;
+pub mod keymap;
+pub mod loader;
+pub mod opcode;
+pub mod recorder;
+pub mod renderer;
+pub mod screenshot;
+pub mod shared_frame;
+pub mod vm;
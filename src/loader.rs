@@ -0,0 +1,262 @@
+//! Reads ROMs from files or stdin, unpacking zip and gzip archives and parsing hex text.
+
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{self, BufRead, Cursor, IsTerminal, Read, Write};
+use std::path::Path;
+
+/// Extensions of the files in an archive that are picked as ROMs, if any.
+const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "rom", "hex", "txt"];
+
+/// Reads the ROM at `path`, or from stdin if it's `-`, asking on the terminal which ROM to load
+/// from an archive holding several.
+pub fn load(path: &str) -> Result<Vec<u8>, String> {
+    let data = if path == "-" {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|error| format!("unable to read ROM from stdin: {}", error))?;
+        data
+    } else {
+        fs::read(path).map_err(|error| format!("unable to read {}: {}", path, error))?
+    };
+    decode(path, data, prompt)
+}
+
+/// Turns the contents of the file called `name` into a ROM. Zip archives and gzip files are
+/// detected by their extension or magic number and unpacked, and hex text is parsed.
+///
+/// `choose` picks which ROM to load from an archive holding several: it's given their names and
+/// returns the index of one.
+pub fn decode(
+    name: &str,
+    data: Vec<u8>,
+    mut choose: impl FnMut(&[String]) -> Result<usize, String>,
+) -> Result<Vec<u8>, String> {
+    unpack(name, data, &mut choose)
+}
+
+/// Parses a hex program address such as `0x600` or `600`, which can't be below 0x200 where the
+/// interpreter and font live.
+pub fn parse_address(address: &str) -> Result<u16, String> {
+    let parsed = u16::from_str_radix(address.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid address: {}", address))?;
+    if parsed < 0x200 {
+        return Err(format!(
+            "invalid address: {} is below 0x200, where the interpreter lives",
+            address
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Octo cartridges hold Octo source, and there's no Octo assembler here.
+const CARTRIDGE_ERROR: &str =
+    "Octo cartridges are not supported; export the program from Octo as a .ch8 instead";
+
+type Choose<'a> = dyn FnMut(&[String]) -> Result<usize, String> + 'a;
+
+fn unpack(name: &str, data: Vec<u8>, choose: &mut Choose) -> Result<Vec<u8>, String> {
+    match extension(name).as_deref() {
+        Some("zip") => unzip(data, choose),
+        Some("gz") => gunzip(name, &data, choose),
+        Some("hex") | Some("txt") => parse_hex(&String::from_utf8_lossy(&data)),
+        Some("gif") => Err(CARTRIDGE_ERROR.to_string()),
+        _ if data.starts_with(b"PK\x03\x04") => unzip(data, choose),
+        _ if data.starts_with(&[0x1f, 0x8b]) => gunzip(name, &data, choose),
+        _ if data.starts_with(b"GIF8") => Err(CARTRIDGE_ERROR.to_string()),
+        _ => match std::str::from_utf8(&data).ok().map(parse_hex) {
+            Some(Ok(rom)) if !rom.is_empty() => Ok(rom),
+            _ => Ok(data),
+        },
+    }
+}
+
+/// Parses whitespace or comma separated hex bytes or words, e.g. `00E0 A22A 600C` or
+/// `0x00, 0xe0`. Comments starting with `;`, `#` or `//` and `200:` style addresses are skipped.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut rom = Vec::new();
+    for line in text.lines() {
+        let line = line
+            .split([';', '#'])
+            .next()
+            .and_then(|line| line.split("//").next())
+            .unwrap_or_default();
+
+        for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() || token.ends_with(':') {
+                continue;
+            }
+            let digits = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if digits.is_empty()
+                || digits.len() % 2 != 0
+                || !digits.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(format!("invalid hex: {:?}", token));
+            }
+            for i in (0..digits.len()).step_by(2) {
+                rom.push(u8::from_str_radix(&digits[i..i + 2], 16).unwrap());
+            }
+        }
+    }
+    Ok(rom)
+}
+
+fn unzip(data: Vec<u8>, choose: &mut Choose) -> Result<Vec<u8>, String> {
+    let zip_error = |error: zip::result::ZipError| format!("invalid zip archive: {}", error);
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(zip_error)?;
+
+    let files: Vec<String> = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    let roms: Vec<String> = files
+        .iter()
+        .filter(|name| {
+            extension(name).is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str()))
+        })
+        .cloned()
+        .collect();
+    let mut names = if roms.is_empty() { files } else { roms };
+    names.sort();
+
+    let name = match names.len() {
+        0 => return Err("zip archive is empty".to_string()),
+        1 => names.remove(0),
+        _ => {
+            let index = choose(&names)?;
+            names
+                .get(index)
+                .cloned()
+                .ok_or_else(|| format!("no ROM {} in the zip archive", index + 1))?
+        }
+    };
+
+    let mut data = Vec::new();
+    archive
+        .by_name(&name)
+        .map_err(zip_error)?
+        .read_to_end(&mut data)
+        .map_err(|error| format!("unable to unzip {}: {}", name, error))?;
+    unpack(&name, data, choose)
+}
+
+fn gunzip(name: &str, data: &[u8], choose: &mut Choose) -> Result<Vec<u8>, String> {
+    let mut unpacked = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut unpacked)
+        .map_err(|error| format!("invalid gzip file: {}", error))?;
+    unpack(name.trim_end_matches(".gz"), unpacked, choose)
+}
+
+/// Lists `names` and asks which one to load, if there's a terminal to ask on.
+fn prompt(names: &[String]) -> Result<usize, String> {
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Err(format!(
+            "the archive holds several ROMs: {}",
+            names.join(", ")
+        ));
+    }
+
+    for (i, name) in names.iter().enumerate() {
+        eprintln!("{:>3}: {}", i + 1, name);
+    }
+    loop {
+        eprint!("ROM to load (1-{}): ", names.len());
+        io::stderr().flush().ok();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            return Err("no ROM chosen".to_string());
+        }
+        let choice = line.trim();
+        if let Some(index) = names.iter().position(|name| name == choice) {
+            return Ok(index);
+        }
+        match choice.parse::<usize>() {
+            Ok(n) if (1..=names.len()).contains(&n) => return Ok(n - 1),
+            _ => eprintln!("expected a number from 1 to {} or a name", names.len()),
+        }
+    }
+}
+
+fn extension(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::SimpleFileOptions;
+
+    fn no_choice(_: &[String]) -> Result<usize, String> {
+        panic!("unexpected choice");
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0x600"), Ok(0x600));
+        assert_eq!(parse_address("200"), Ok(0x200));
+        assert!(parse_address("100").is_err());
+        assert!(parse_address("0").is_err());
+        assert!(parse_address("0x10000").is_err());
+        assert!(parse_address("hello").is_err());
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(
+            parse_hex("200: 00E0 a22a ; CLS, LD I\n0x60, 0x0c // LD V0\n").unwrap(),
+            vec![0x00, 0xe0, 0xa2, 0x2a, 0x60, 0x0c]
+        );
+        assert!(parse_hex("00E").is_err());
+        assert!(parse_hex("hello").is_err());
+
+        // Binary ROMs aren't mistaken for text
+        let rom = vec![0x00, 0xe0, 0x12, 0x00];
+        assert_eq!(decode("rom.ch8", rom.clone(), no_choice).unwrap(), rom);
+        assert_eq!(decode("-", b"00e0 1200".to_vec(), no_choice).unwrap(), rom);
+
+        // Octo cartridges are refused rather than run as binary
+        assert!(decode("-", b"GIF89a".to_vec(), no_choice).is_err());
+    }
+
+    #[test]
+    fn test_archives() {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b"00e0 1200").unwrap();
+        let gz = gz.finish().unwrap();
+        assert_eq!(
+            decode("rom.hex.gz", gz.clone(), no_choice).unwrap(),
+            vec![0x00, 0xe0, 0x12, 0x00]
+        );
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in [
+            ("readme.md", b"# Games".as_slice()),
+            ("b.ch8", &[0xbb]),
+            ("a.ch8", &[0xaa]),
+        ] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        let zip = zip.finish().unwrap().into_inner();
+
+        let mut offered = Vec::new();
+        let rom = decode("-", zip, |names| {
+            offered = names.to_vec();
+            Ok(1)
+        })
+        .unwrap();
+        assert_eq!(offered, vec!["a.ch8", "b.ch8"]);
+        assert_eq!(rom, vec![0xbb]);
+    }
+}
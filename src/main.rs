@@ -1,73 +1,651 @@
-mod opcode;
-mod vm;
-
+use chip_8::config;
+use chip_8::database;
+use chip_8::keymap::{Keymap, HOST_KEYS};
+use chip_8::loader;
+use chip_8::recorder::Recorder;
+use chip_8::renderer::{Filter, Renderer};
+use chip_8::shared_frame::SharedFrame;
+use chip_8::vm::trace::{self, Tracer};
+use chip_8::vm::{FrameBuffer, Key, OnUnknown, State, Vm};
 use clap::{App, Arg};
-use minifb::{Scale, Window, WindowOptions};
-use std::fs::File;
-use std::io::Read;
-use std::sync::mpsc;
+use minifb::{KeyRepeat, Scale, Window, WindowOptions};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, LineWriter};
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
 use std::thread::{self, sleep};
-use std::time::{Duration, Instant};
-use vm::{Key, Vm, HEIGHT, WIDTH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const FRAME: Duration = Duration::from_micros(16_667);
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
 
 fn main() {
     let matches = App::new("LC-3 VM")
         .arg(
             Arg::with_name("PROGRAM")
-                .help("The program to run.")
+                .help("The program to run: a ROM, hex text, .zip or .gz, or - for stdin.")
                 .required(true)
                 .index(1),
         )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .help("Reload the program whenever it changes on disk."),
+        )
+        .arg(
+            Arg::with_name("scale")
+                .long("scale")
+                .value_name("N")
+                .help(
+                    "Scale the window up by 1, 2, 4, 8, 16 or 32 [default: 8, or 2 for MegaChip].",
+                )
+                .possible_values(&["1", "2", "4", "8", "16", "32"]),
+        )
+        .arg(
+            Arg::with_name("fast-forward")
+                .long("fast-forward")
+                .value_name("N")
+                .help("Run N times faster while Tab is held, or as fast as possible if 0.")
+                .validator(|n| n.parse::<u32>().map(|_| ()).map_err(|e| e.to_string()))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("on-unknown")
+                .long("on-unknown")
+                .help("What to do when an unknown instruction is fetched.")
+                .possible_values(&["halt", "ignore", "break"])
+                .default_value("halt"),
+        )
+        .arg(
+            Arg::with_name("phosphor")
+                .long("phosphor")
+                .value_name("DECAY")
+                .help("Fade pixels out, keeping DECAY (0 to 1) of their brightness every frame.")
+                .validator(|decay| match decay.parse::<f32>() {
+                    Ok(decay) if (0.0..=1.0).contains(&decay) => Ok(()),
+                    _ => Err("expected a number from 0 to 1".to_string()),
+                })
+                .conflicts_with("persistence"),
+        )
+        .arg(
+            Arg::with_name("persistence")
+                .long("persistence")
+                .value_name("FRAMES")
+                .help("Keep pixels lit while they're on in any of the last FRAMES frames."),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .value_name("FILE")
+                .help("Write a line to FILE for every executed instruction."),
+        )
+        .arg(
+            Arg::with_name("trace-format")
+                .long("trace-format")
+                .help("The format of the trace file.")
+                .possible_values(&["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::with_name("screenshot-scale")
+                .long("screenshot-scale")
+                .value_name("N")
+                .help("Scale screenshots (F12) up by N.")
+                .default_value("8"),
+        )
+        .arg(
+            Arg::with_name("record-scale")
+                .long("record-scale")
+                .value_name("N")
+                .help("Scale GIF recordings (F10) up by N.")
+                .default_value("4"),
+        )
+        .arg(
+            Arg::with_name("record-raw")
+                .long("record-raw")
+                .help("Also write raw rgb24 frames at 60 fps alongside GIF recordings."),
+        )
+        .args(&config::args())
+        .args(&config::play_args())
         .get_matches();
 
-    let mut rom = Vec::new();
-    File::open(matches.value_of("PROGRAM").unwrap())
-        .expect("unable to open ROM")
-        .read_to_end(&mut rom)
-        .expect("unable to read ROM");
+    let on_unknown = match matches.value_of("on-unknown") {
+        Some("ignore") => OnUnknown::Ignore,
+        Some("break") => OnUnknown::Break,
+        _ => OnUnknown::Halt,
+    };
 
-    let (tx_buf, rx_buf) = mpsc::channel::<Vec<u32>>();
-    let (tx_key, rx_key) = mpsc::channel::<Option<Key>>();
+    let tracer = matches.value_of("trace").map(|path| {
+        let format = match matches.value_of("trace-format") {
+            Some("json") => trace::Format::Json,
+            _ => trace::Format::Text,
+        };
+        let file = File::create(path).expect("unable to create trace file");
+        Tracer::new(Box::new(LineWriter::new(file)), format)
+    });
 
-    thread::spawn(move || {
-        let mut vm = Vm::new(rx_key);
-        vm.load_rom(&rom);
+    let filter = if let Some(decay) = matches.value_of("phosphor") {
+        Filter::Phosphor {
+            decay: decay.parse().unwrap(),
+        }
+    } else if let Some(frames) = matches.value_of("persistence") {
+        Filter::Persistence {
+            frames: frames.parse().expect("invalid persistence"),
+        }
+    } else {
+        Filter::None
+    };
 
-        let mut last_instant = Instant::now();
-        loop {
-            sleep(Duration::from_micros(1660) - last_instant.elapsed());
+    let screenshot_scale = matches
+        .value_of("screenshot-scale")
+        .unwrap()
+        .parse()
+        .expect("invalid screenshot scale");
+    let record_scale = matches
+        .value_of("record-scale")
+        .unwrap()
+        .parse()
+        .expect("invalid record scale");
+    let record_raw = matches.is_present("record-raw");
+    let fast_forward = match matches.value_of("fast-forward").unwrap().parse().unwrap() {
+        0 => Pace::Uncapped,
+        multiple => Pace::Multiple(multiple),
+    };
 
-            let instruction = vm.fetch();
-            let opcode = opcode::decode(instruction);
-            vm.execute(opcode);
+    let program = matches.value_of("PROGRAM").unwrap();
+    let rom = loader::load(program).unwrap_or_else(|error| exit(&error));
+    let mut watch = if matches.is_present("watch") {
+        Some(Watch::new(program))
+    } else {
+        None
+    };
 
-            tx_buf
-                .send(vm.frame_buffer.buffer().clone())
-                .expect("unable to send buffer");
+    let settings = config::load_args(&matches, &rom).unwrap_or_else(|error| exit(&error));
 
-            last_instant = Instant::now();
-        }
-    });
+    let title = database::title(&config::rom_hash(&rom));
+
+    let quirks = settings.quirks();
+    let cycles_per_frame = settings.speed.unwrap_or(10);
+    let audio = settings.audio.unwrap_or(true);
+    let palette = settings.palette().unwrap_or_else(|error| exit(&error));
+    // Keep the window about 512 pixels wide by default, whatever the display's width
+    let (display_width, _) = settings
+        .platform
+        .unwrap_or_default()
+        .variant()
+        .display_size();
+    let scale = window_scale(settings.scale.unwrap_or(512 / display_width))
+        .unwrap_or_else(|error| exit(&error));
+    let keymap = settings.keymap().unwrap_or_else(|error| exit(&error));
+    let keypad = bindings(&keymap);
+    let keymap2 = settings.keymap2().unwrap_or_else(|error| exit(&error));
+    let keypad2 = bindings(&keymap2);
+
+    let (tx_key, rx_key) = mpsc::channel::<Option<Key>>();
+    let (tx_key2, rx_key2) = mpsc::channel::<Option<Key>>();
+    // Only CHIP-8X programs read the second keypad, so it's sent when it changes
+    let mut key2_sent = None;
+
+    let (tx_command, rx_command) = mpsc::channel();
+    let (tx_paused, rx_paused) = mpsc::channel();
+
+    let mut vm = Vm::new(rx_key);
+    vm.connect_keypad2(rx_key2);
+    vm.on_unknown = on_unknown;
+    vm.quirks = quirks;
+    vm.cycles_per_frame = cycles_per_frame;
+    vm.set_platform(settings.platform.unwrap_or_default());
+    if let Some(address) = config::load_address(&matches) {
+        vm.load_address = address;
+    }
+    if let Some(tracer) = tracer {
+        vm.trace(tracer);
+    }
+    vm.load_rom(&rom)
+        .unwrap_or_else(|error| exit(&error.to_string()));
+
+    let (width, height) = (vm.frame_buffer.width(), vm.frame_buffer.height());
+    let mut renderer = Renderer::new(palette)
+        .with_filter(filter)
+        .with_pixel_aspect(vm.platform().variant().pixel_aspect());
+    let shared_frame = Arc::new(SharedFrame::new(width, height));
+    let emulator_frame = Arc::clone(&shared_frame);
+    let emulator =
+        thread::spawn(move || emulate(vm, rom, &rx_command, &tx_paused, &emulator_frame, audio));
 
     let window_options = WindowOptions {
-        scale: Scale::X8,
+        scale,
         ..WindowOptions::default()
     };
-    let mut window =
-        Window::new("CHIP-8", WIDTH, HEIGHT, window_options).expect("could open window");
+    let mut controls = Controls {
+        paused: false,
+        pace: Pace::Normal,
+        cycles_per_frame,
+    };
+    let mut window = Window::new(
+        &format!("{} - {}", title, controls),
+        width * renderer.pixel_aspect,
+        height,
+        window_options,
+    )
+    .expect("could open window");
 
+    let mut frame_buffer = FrameBuffer::new(width, height);
+    let mut recorder: Option<Recorder<BufWriter<File>>> = None;
     while window.is_open() {
-        window.get_keys().map(|keys| {
-            tx_key
-                .send(keys.iter().find_map(|k| Key::from(*k)))
-                .expect("key send failed")
-        });
+        let previous = controls;
+        if let Some(paused) = rx_paused.try_iter().last() {
+            controls.paused = paused;
+        }
+        let mut commands = Vec::new();
+        if window.is_key_pressed(minifb::Key::F5, KeyRepeat::No) {
+            controls.paused = !controls.paused;
+            commands.push(Command::Pause(controls.paused));
+        }
+        if window.is_key_pressed(minifb::Key::F6, KeyRepeat::Yes) {
+            controls.paused = true;
+            commands.push(Command::AdvanceFrame);
+        }
+        if window.is_key_pressed(minifb::Key::F7, KeyRepeat::Yes) {
+            controls.paused = true;
+            commands.push(Command::Step);
+        }
+        controls.pace = if window.is_key_down(minifb::Key::Tab) {
+            fast_forward
+        } else {
+            Pace::Normal
+        };
+        if controls.pace != previous.pace {
+            commands.push(Command::Pace(controls.pace));
+        }
+        if window.is_key_pressed(minifb::Key::F8, KeyRepeat::No) {
+            commands.push(Command::Reset);
+        }
+        if let Some(rom) = watch.as_mut().and_then(Watch::changed) {
+            eprintln!("reloading {}", program);
+            commands.push(Command::Load(rom));
+        }
+        if window.is_key_pressed(minifb::Key::PageUp, KeyRepeat::Yes) {
+            controls.cycles_per_frame += 1;
+        }
+        if window.is_key_pressed(minifb::Key::PageDown, KeyRepeat::Yes) {
+            controls.cycles_per_frame = controls.cycles_per_frame.saturating_sub(1).max(1);
+        }
+        if controls.cycles_per_frame != previous.cycles_per_frame {
+            commands.push(Command::CyclesPerFrame(controls.cycles_per_frame));
+        }
+        if controls != previous {
+            window.set_title(&format!("{} - {}", title, controls));
+        }
+
+        // The emulator thread only hangs up if it panicked
+        let keys = window.get_keys();
+        let key = keys
+            .as_ref()
+            .map(|keys| keys.iter().find_map(|k| keypad.get(k).copied()));
+        let key2 = keys
+            .as_ref()
+            .map(|keys| keys.iter().find_map(|k| keypad2.get(k).copied()))
+            .filter(|key2| *key2 != key2_sent);
+        let sent = commands
+            .into_iter()
+            .all(|command| tx_command.send(command).is_ok());
+        if !sent
+            || key.is_some_and(|key| tx_key.send(key).is_err())
+            || key2.is_some_and(|key2| tx_key2.send(key2).is_err())
+        {
+            eprintln!("emulator stopped: {}", panic_message(emulator.join()));
+            break;
+        }
+        key2_sent = key2.unwrap_or(key2_sent);
 
-        match rx_buf.try_iter().last() {
-            Some(buffer) => window
-                .update_with_buffer(&buffer)
-                .expect("could not update buffer"),
+        match shared_frame.take(&mut frame_buffer) {
+            Some(frame) => {
+                if let Some(active) = recorder.as_mut() {
+                    if let Err(error) = active.capture(frame, &renderer.render(&frame_buffer)) {
+                        eprintln!("unable to record: {}", error);
+                        recorder = None;
+                    }
+                }
+                window
+                    .update_with_buffer(&renderer.present(&frame_buffer))
+                    .expect("could not update buffer");
+            }
             None => window.update(),
         }
+
+        if window.is_key_pressed(minifb::Key::F12, KeyRepeat::No) {
+            save_screenshot(&frame_buffer, &renderer, screenshot_scale);
+        }
+
+        if window.is_key_pressed(minifb::Key::F10, KeyRepeat::No) {
+            recorder = match recorder.take() {
+                Some(recorder) => {
+                    stop_recording(recorder);
+                    None
+                }
+                None => start_recording(&frame_buffer, &renderer, record_scale, record_raw),
+            };
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        stop_recording(recorder);
+    }
+}
+
+/// Sent from the window to the emulator thread.
+enum Command {
+    Pause(bool),
+    /// Pause after running one more frame.
+    AdvanceFrame,
+    /// Pause after executing one more instruction.
+    Step,
+    Pace(Pace),
+    CyclesPerFrame(usize),
+    /// Reset the VM and load the ROM again.
+    Reset,
+    /// Reset the VM and load a new ROM.
+    Load(Vec<u8>),
+}
+
+/// How fast frames are run relative to 60 Hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pace {
+    Normal,
+    Multiple(u32),
+    Uncapped,
+}
+
+/// The state of the hotkeys, shown in the window title.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Controls {
+    paused: bool,
+    pace: Pace,
+    cycles_per_frame: usize,
+}
+
+impl fmt::Display for Controls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} cycles/frame", self.cycles_per_frame)?;
+        if self.paused {
+            write!(f, " - paused")
+        } else {
+            match self.pace {
+                Pace::Normal => Ok(()),
+                Pace::Multiple(multiple) => write!(f, " - x{}", multiple),
+                Pace::Uncapped => write!(f, " - uncapped"),
+            }
+        }
+    }
+}
+
+/// Runs `vm`, with `rom` loaded, a frame at a time, publishing every frame to `shared_frame`.
+///
+/// A stopped VM waits to be reset or given a new ROM. A VM that breaks pauses, sending `true` to
+/// `tx_paused`, so it can be stepped through from there.
+fn emulate(
+    mut vm: Vm,
+    mut rom: Vec<u8>,
+    rx_command: &Receiver<Command>,
+    tx_paused: &Sender<bool>,
+    shared_frame: &SharedFrame,
+    audio: bool,
+) {
+    let mut stopped = false;
+    let mut paused = false;
+    let mut pace = Pace::Normal;
+    let mut deadline = Instant::now();
+    loop {
+        // Block while paused rather than spinning
+        let mut commands: Vec<_> = rx_command.try_iter().collect();
+        if (paused || stopped) && commands.is_empty() {
+            match rx_command.recv() {
+                Ok(command) => commands.push(command),
+                Err(_) => return,
+            }
+            deadline = Instant::now();
+        }
+
+        let mut step = false;
+        let mut advance = false;
+        let mut reset = false;
+        for command in commands {
+            match command {
+                Command::Pause(pause) => paused = pause,
+                Command::AdvanceFrame => advance = true,
+                Command::Step => step = true,
+                Command::Pace(new_pace) => pace = new_pace,
+                Command::CyclesPerFrame(cycles_per_frame) => {
+                    vm.cycles_per_frame = cycles_per_frame;
+                }
+                Command::Reset => reset = true,
+                Command::Load(new_rom) => {
+                    rom = new_rom;
+                    reset = true;
+                }
+            }
+        }
+        if reset {
+            vm.reset();
+            stopped = match vm.load_rom(&rom) {
+                Ok(()) => false,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    true
+                }
+            };
+            shared_frame.publish(vm.frames(), &vm.frame_buffer);
+        }
+        if advance || step {
+            paused = true;
+        }
+        if stopped || (paused && !advance && !step) {
+            continue;
+        }
+
+        let sound = vm.sound();
+        let state = if step { vm.step() } else { vm.frame() };
+        shared_frame.publish(vm.frames(), &vm.frame_buffer);
+
+        // Ring the terminal bell when the buzzer starts
+        if audio && vm.sound() && !sound {
+            eprint!("\x07");
+        }
+
+        match state {
+            State::Running => {}
+            State::Halted(error) => {
+                eprintln!("halted at {:#05x}: {}", vm.pc, error);
+                stopped = true;
+            }
+            State::Break(error) => {
+                eprintln!("break at {:#05x}: {}", vm.pc, error);
+                paused = true;
+                if tx_paused.send(true).is_err() {
+                    return;
+                }
+            }
+        }
+
+        // Pace frames from the emulated clock, catching up after short stalls
+        deadline += match pace {
+            Pace::Normal => FRAME,
+            Pace::Multiple(multiple) => FRAME / multiple,
+            Pace::Uncapped => Duration::from_secs(0),
+        };
+        let now = Instant::now();
+        if deadline > now {
+            sleep(deadline - now);
+        } else if now - deadline > FRAME * 10 || pace == Pace::Uncapped {
+            deadline = now;
+        }
+    }
+}
+
+/// Polls a file for changes to its modification time.
+struct Watch {
+    path: String,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl Watch {
+    fn new(path: &str) -> Self {
+        let path = path.to_string();
+        Self {
+            modified: modified(Path::new(&path)),
+            path,
+            checked: Instant::now(),
+        }
+    }
+
+    /// The new contents of the file if it changed since the last call, checking at most every
+    /// `WATCH_INTERVAL`.
+    fn changed(&mut self) -> Option<Vec<u8>> {
+        if self.checked.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.checked = Instant::now();
+
+        let modified = modified(Path::new(&self.path));
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        match loader::load(&self.path) {
+            Ok(rom) => Some(rom),
+            Err(error) => {
+                eprintln!("unable to reload ROM: {}", error);
+                None
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The window's keys for each of `keymap::HOST_KEYS`, in the same order.
+#[rustfmt::skip]
+const WINDOW_KEYS: [minifb::Key; 64] = {
+    use minifb::Key::*;
+    [
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        A, B, C, D, E, F, G, H, I, J, K, L, M,
+        N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Up, Down, Left, Right, Space, Enter, Backspace,
+        Apostrophe, Backquote, Backslash, Comma, Equal, LeftBracket, Minus,
+        Period, RightBracket, Semicolon, Slash,
+        NumPad0, NumPad1, NumPad2, NumPad3, NumPad4,
+        NumPad5, NumPad6, NumPad7, NumPad8, NumPad9,
+    ]
+};
+
+/// The keypad keys bound to host keys in `keymap`.
+fn bindings(keymap: &Keymap) -> HashMap<minifb::Key, Key> {
+    WINDOW_KEYS
+        .iter()
+        .zip(HOST_KEYS)
+        .filter_map(|(host, name)| keymap.get(name).map(|key| (*host, key)))
+        .collect()
+}
+
+/// What a thread panicked with, given the result of joining it.
+fn panic_message(result: thread::Result<()>) -> String {
+    match result {
+        Ok(()) => "exited".to_string(),
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panicked".to_string()),
+    }
+}
+
+fn window_scale(scale: usize) -> Result<Scale, String> {
+    match scale {
+        1 => Ok(Scale::X1),
+        2 => Ok(Scale::X2),
+        4 => Ok(Scale::X4),
+        8 => Ok(Scale::X8),
+        16 => Ok(Scale::X16),
+        32 => Ok(Scale::X32),
+        _ => Err(format!(
+            "invalid scale: {} (expected 1, 2, 4, 8, 16 or 32)",
+            scale
+        )),
+    }
+}
+
+fn exit(error: &str) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+fn timestamped_path(extension: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    format!("chip-8-{}.{}", timestamp, extension)
+}
+
+fn start_recording(
+    frame_buffer: &FrameBuffer,
+    renderer: &Renderer,
+    scale: usize,
+    raw: bool,
+) -> Option<Recorder<BufWriter<File>>> {
+    let (width, height) = (renderer.width(frame_buffer), frame_buffer.height());
+    let path = timestamped_path("gif");
+    let recorder = File::create(&path)
+        .and_then(|file| Recorder::new(width, height, scale).gif(BufWriter::new(file)))
+        .and_then(|recorder| {
+            if raw {
+                let raw_path = path.replace(".gif", ".rgb");
+                let file = File::create(&raw_path)?;
+                eprintln!("recording raw frames to {}", raw_path);
+                Ok(recorder.raw(BufWriter::new(file)))
+            } else {
+                Ok(recorder)
+            }
+        });
+
+    match recorder {
+        Ok(recorder) => {
+            eprintln!("recording to {}", path);
+            Some(recorder)
+        }
+        Err(error) => {
+            eprintln!("unable to start recording: {}", error);
+            None
+        }
+    }
+}
+
+fn stop_recording(recorder: Recorder<BufWriter<File>>) {
+    match recorder.finish() {
+        Ok(()) => eprintln!("stopped recording"),
+        Err(error) => eprintln!("unable to finish recording: {}", error),
+    }
+}
+
+fn save_screenshot(frame_buffer: &FrameBuffer, renderer: &Renderer, scale: usize) {
+    let path = timestamped_path("png");
+
+    match renderer
+        .to_png(frame_buffer, scale)
+        .and_then(|png| fs::write(&path, png))
+    {
+        Ok(()) => eprintln!("saved screenshot to {}", path),
+        Err(error) => eprintln!("unable to save screenshot: {}", error),
     }
 }
@@ -1,6 +1,12 @@
+use std::error::Error;
+use std::fmt;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub enum Opcode {
     DisplayClear,
+    /// `0230`, which clears the 64x64 display of the CHIP-8 hi-res interpreter.
+    DisplayClearHiRes,
     RET,
     SKP(DataRegister),
     SKNP(DataRegister),
@@ -35,6 +41,36 @@ pub enum Opcode {
     JPB(Nnn),
     DRW(DataRegister, DataRegister, N),
     RND(DataRegister, Kk),
+    /// CHIP-8X `02A0`: moves on to the next background colour.
+    CycleBackground,
+    /// CHIP-8X `BXYN`: colours the zones given by VX and VX+1 with the colour in VY.
+    COL(DataRegister, DataRegister, N),
+    /// CHIP-8X `5XY1`: adds VY to VX nibble by nibble, each wrapping at 8.
+    AddNibbles(DataRegister, DataRegister),
+    /// CHIP-8X `EXF2` and `EXF5`: skip depending on the key in VX on the second keypad.
+    SKP2(DataRegister),
+    SKNP2(DataRegister),
+    /// MegaChip `0010` and `0011`: switch mega mode off and on.
+    MEGAOFF,
+    MEGAON,
+    /// MegaChip `01NN NNNN`: loads a 24 bit address into I, the high byte from here and the rest
+    /// from the next word.
+    LDHI(Kk),
+    /// MegaChip `02NN`: loads NN `0xAARRGGBB` colours from I into the palette, from index 1.
+    LDPAL(Kk),
+    /// MegaChip `03NN` and `04NN`: set the width and height of sprites, with 0 meaning 256.
+    SPRW(Kk),
+    SPRH(Kk),
+    /// MegaChip `05NN`: sets the opacity of the display.
+    ALPHA(Kk),
+    /// MegaChip `060N`: plays the sample at I, once or, if N is 0, looping.
+    DIGISND(N),
+    /// MegaChip `0700`: stops the sample.
+    STOPSND,
+    /// MegaChip `080N`: sets how sprites are blended.
+    BMODE(N),
+    /// MegaChip `09NN`: sets the colour index sprites collide with.
+    CCOL(Kk),
 }
 
 #[rustfmt::skip]
@@ -46,13 +82,13 @@ pub enum DataRegister {
 
 #[rustfmt::skip]
 impl DataRegister {
+    /// Only the low nibble of `n` is used.
     pub fn from(n: u8) -> Self {
-        match n {
+        match n & 0xf {
             0x0 => DataRegister::V0, 0x1 => DataRegister::V1, 0x2 => DataRegister::V2, 0x3 => DataRegister::V3,
             0x4 => DataRegister::V4, 0x5 => DataRegister::V5, 0x6 => DataRegister::V6, 0x7 => DataRegister::V7,
             0x8 => DataRegister::V8, 0x9 => DataRegister::V9, 0xa => DataRegister::VA, 0xb => DataRegister::VB,
-            0xc => DataRegister::VC, 0xd => DataRegister::VD, 0xe => DataRegister::VE, 0xf => DataRegister::VF,
-            _ => panic!("invalid data register: {}", n),
+            0xc => DataRegister::VC, 0xd => DataRegister::VD, 0xe => DataRegister::VE, _ => DataRegister::VF,
         }
     }
 }
@@ -66,6 +102,77 @@ pub struct Kk(pub u8);
 #[derive(Debug)]
 pub struct N(pub u8);
 
+/// Disassembles to the mnemonics used in Cowgod's Chip-8 technical reference.
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Opcode::DisplayClear => write!(f, "CLS"),
+            Opcode::DisplayClearHiRes => write!(f, "HCLS"),
+            Opcode::RET => write!(f, "RET"),
+            Opcode::SKP(x) => write!(f, "SKP {:?}", x),
+            Opcode::SKNP(x) => write!(f, "SKNP {:?}", x),
+            Opcode::CALL(Nnn(nnn)) => write!(f, "CALL {:#05x}", nnn),
+            Opcode::SYS(Nnn(nnn)) => write!(f, "SYS {:#05x}", nnn),
+            Opcode::LDI(Nnn(nnn)) => write!(f, "LD I, {:#05x}", nnn),
+            Opcode::LD6(x, Kk(kk)) => write!(f, "LD {:?}, {:#04x}", x, kk),
+            Opcode::LD8(x, y) => write!(f, "LD {:?}, {:?}", x, y),
+            Opcode::LdDtToReg(x) => write!(f, "LD {:?}, DT", x),
+            Opcode::LdDt(x) => write!(f, "LD DT, {:?}", x),
+            Opcode::LdSt(x) => write!(f, "LD ST, {:?}", x),
+            Opcode::LdB(x) => write!(f, "LD B, {:?}", x),
+            Opcode::LdF(x) => write!(f, "LD F, {:?}", x),
+            Opcode::LdAll(x) => write!(f, "LD {:?}, [I]", x),
+            Opcode::LdAllI(x) => write!(f, "LD [I], {:?}", x),
+            Opcode::LdKey(x) => write!(f, "LD {:?}, K", x),
+            Opcode::OR8(x, y) => write!(f, "OR {:?}, {:?}", x, y),
+            Opcode::AND8(x, y) => write!(f, "AND {:?}, {:?}", x, y),
+            Opcode::XOR8(x, y) => write!(f, "XOR {:?}, {:?}", x, y),
+            Opcode::ADD8(x, y) => write!(f, "ADD {:?}, {:?}", x, y),
+            Opcode::ADD(x, Kk(kk)) => write!(f, "ADD {:?}, {:#04x}", x, kk),
+            Opcode::AddI(x) => write!(f, "ADD I, {:?}", x),
+            Opcode::SUB8(x, y) => write!(f, "SUB {:?}, {:?}", x, y),
+            Opcode::SHR8(x, y) => write!(f, "SHR {:?}, {:?}", x, y),
+            Opcode::SUBN8(x, y) => write!(f, "SUBN {:?}, {:?}", x, y),
+            Opcode::SHL8(x, y) => write!(f, "SHL {:?}, {:?}", x, y),
+            Opcode::SNE4(x, Kk(kk)) => write!(f, "SNE {:?}, {:#04x}", x, kk),
+            Opcode::SE5(x, y) => write!(f, "SE {:?}, {:?}", x, y),
+            Opcode::SNE(x, y) => write!(f, "SNE {:?}, {:?}", x, y),
+            Opcode::SE3(x, Kk(kk)) => write!(f, "SE {:?}, {:#04x}", x, kk),
+            Opcode::JP(Nnn(nnn)) => write!(f, "JP {:#05x}", nnn),
+            Opcode::JPB(Nnn(nnn)) => write!(f, "JP V0, {:#05x}", nnn),
+            Opcode::DRW(x, y, N(n)) => write!(f, "DRW {:?}, {:?}, {}", x, y, n),
+            Opcode::RND(x, Kk(kk)) => write!(f, "RND {:?}, {:#04x}", x, kk),
+            Opcode::CycleBackground => write!(f, "BGC"),
+            Opcode::COL(x, y, N(n)) => write!(f, "COL {:?}, {:?}, {}", x, y, n),
+            Opcode::AddNibbles(x, y) => write!(f, "ADDN {:?}, {:?}", x, y),
+            Opcode::SKP2(x) => write!(f, "SKP2 {:?}", x),
+            Opcode::SKNP2(x) => write!(f, "SKNP2 {:?}", x),
+            Opcode::MEGAOFF => write!(f, "MEGAOFF"),
+            Opcode::MEGAON => write!(f, "MEGAON"),
+            Opcode::LDHI(Kk(kk)) => write!(f, "LDHI I, {:#04x}....", kk),
+            Opcode::LDPAL(Kk(kk)) => write!(f, "LDPAL {}", kk),
+            Opcode::SPRW(Kk(kk)) => write!(f, "SPRW {}", kk),
+            Opcode::SPRH(Kk(kk)) => write!(f, "SPRH {}", kk),
+            Opcode::ALPHA(Kk(kk)) => write!(f, "ALPHA {:#04x}", kk),
+            Opcode::DIGISND(N(n)) => write!(f, "DIGISND {}", n),
+            Opcode::STOPSND => write!(f, "STOPSND"),
+            Opcode::BMODE(N(n)) => write!(f, "BMODE {}", n),
+            Opcode::CCOL(Kk(kk)) => write!(f, "CCOL {:#04x}", kk),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeError(pub u16);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid instruction: {:#06x}", self.0)
+    }
+}
+
+impl Error for DecodeError {}
+
 fn decode_parts(instruction: u16) -> (Nnn, Kk, DataRegister, DataRegister, N) {
     (
         Nnn(instruction & 0x0fff),
@@ -76,10 +183,10 @@ fn decode_parts(instruction: u16) -> (Nnn, Kk, DataRegister, DataRegister, N) {
     )
 }
 
-pub fn decode(instruction: u16) -> Opcode {
+pub fn decode(instruction: u16) -> Result<Opcode, DecodeError> {
     let (nnn, kk, x, y, n) = decode_parts(instruction);
 
-    match (instruction >> 12) & 0xf {
+    let opcode = match (instruction >> 12) & 0xf {
         0x0 => match instruction {
             0x00e0 => Opcode::DisplayClear,
             0x00ee => Opcode::RET,
@@ -102,7 +209,7 @@ pub fn decode(instruction: u16) -> Opcode {
             0x6 => Opcode::SHR8(x, y),
             0x7 => Opcode::SUBN8(x, y),
             0xE => Opcode::SHL8(x, y),
-            _ => invalid_instruction(instruction),
+            _ => return Err(DecodeError(instruction)),
         },
         0x9 => Opcode::SNE(x, y),
         0xa => Opcode::LDI(nnn),
@@ -112,7 +219,7 @@ pub fn decode(instruction: u16) -> Opcode {
         0xe => match kk.0 {
             0x9e => Opcode::SKP(x),
             0xa1 => Opcode::SKNP(x),
-            _ => invalid_instruction(instruction),
+            _ => return Err(DecodeError(instruction)),
         },
         0xf => match kk.0 {
             0x07 => Opcode::LdDtToReg(x),
@@ -124,14 +231,59 @@ pub fn decode(instruction: u16) -> Opcode {
             0x33 => Opcode::LdB(x),
             0x55 => Opcode::LdAllI(x),
             0x65 => Opcode::LdAll(x),
-            _ => invalid_instruction(instruction),
+            _ => return Err(DecodeError(instruction)),
         },
-        _ => invalid_instruction(instruction),
+        _ => return Err(DecodeError(instruction)),
+    };
+
+    Ok(opcode)
+}
+
+/// Decodes the CHIP-8 hi-res interpreter's `0230` on top of the standard set.
+pub fn decode_hires(instruction: u16) -> Result<Opcode, DecodeError> {
+    match instruction {
+        0x0230 => Ok(Opcode::DisplayClearHiRes),
+        _ => decode(instruction),
     }
 }
 
-fn invalid_instruction(instruction: u16) -> ! {
-    panic!("invalid instruction: {:#06x}", instruction);
+/// Decodes CHIP-8X, which replaces `JP V0, nnn` with `BXYN` and adds `02A0`, `5XY1`, `EXF2` and
+/// `EXF5` to the standard set.
+pub fn decode_chip8x(instruction: u16) -> Result<Opcode, DecodeError> {
+    let (_, kk, x, y, n) = decode_parts(instruction);
+
+    let opcode = match (instruction >> 12) & 0xf {
+        0x0 if instruction == 0x02a0 => Opcode::CycleBackground,
+        0x5 if n.0 == 1 => Opcode::AddNibbles(x, y),
+        0xb => Opcode::COL(x, y, n),
+        0xe if kk.0 == 0xf2 => Opcode::SKP2(x),
+        0xe if kk.0 == 0xf5 => Opcode::SKNP2(x),
+        _ => return decode(instruction),
+    };
+
+    Ok(opcode)
+}
+
+/// Decodes the MegaChip instructions that start with 0, on top of the standard set.
+pub fn decode_megachip(instruction: u16) -> Result<Opcode, DecodeError> {
+    let (_, kk, _, _, n) = decode_parts(instruction);
+
+    let opcode = match instruction >> 8 {
+        0x00 if instruction == 0x0010 => Opcode::MEGAOFF,
+        0x00 if instruction == 0x0011 => Opcode::MEGAON,
+        0x01 => Opcode::LDHI(kk),
+        0x02 => Opcode::LDPAL(kk),
+        0x03 => Opcode::SPRW(kk),
+        0x04 => Opcode::SPRH(kk),
+        0x05 => Opcode::ALPHA(kk),
+        0x06 if kk.0 >> 4 == 0 => Opcode::DIGISND(n),
+        0x07 if kk.0 == 0 => Opcode::STOPSND,
+        0x08 if kk.0 >> 4 == 0 => Opcode::BMODE(n),
+        0x09 => Opcode::CCOL(kk),
+        _ => return decode(instruction),
+    };
+
+    Ok(opcode)
 }
 
 #[cfg(test)]
@@ -148,4 +300,53 @@ mod tests {
         assert_eq!(y, DataRegister::VC); // 12 (0b1100)
         assert_eq!(n.0, 0b1000);
     }
+
+    #[test]
+    fn test_decode_unknown() {
+        assert!(decode(0x00e0).is_ok());
+        assert_eq!(decode(0x8008).unwrap_err(), DecodeError(0x8008));
+        assert_eq!(decode(0xe000).unwrap_err(), DecodeError(0xe000));
+        assert_eq!(decode(0xf0ff).unwrap_err(), DecodeError(0xf0ff));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let disassemble = |instruction| decode(instruction).unwrap().to_string();
+
+        assert_eq!(disassemble(0x00e0), "CLS");
+        assert_eq!(disassemble(0x0230), "SYS 0x230");
+        assert_eq!(decode_hires(0x0230).unwrap().to_string(), "HCLS");
+        assert_eq!(disassemble(0x1228), "JP 0x228");
+        assert_eq!(disassemble(0x610a), "LD V1, 0x0a");
+        assert_eq!(disassemble(0x8ab4), "ADD VA, VB");
+        assert_eq!(disassemble(0xd125), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0xf355), "LD [I], V3");
+    }
+
+    #[test]
+    fn test_decode_chip8x() {
+        let disassemble = |instruction| decode_chip8x(instruction).unwrap().to_string();
+
+        assert_eq!(disassemble(0x02a0), "BGC");
+        assert_eq!(disassemble(0xb120), "COL V1, V2, 0");
+        assert_eq!(disassemble(0x5121), "ADDN V1, V2");
+        assert_eq!(disassemble(0x5120), "SE V1, V2");
+        assert_eq!(disassemble(0xe3f2), "SKP2 V3");
+        assert_eq!(disassemble(0xe3a1), "SKNP V3");
+        assert_eq!(disassemble(0x00e0), "CLS");
+        assert_eq!(decode(0xb120).unwrap().to_string(), "JP V0, 0x120");
+    }
+
+    #[test]
+    fn test_decode_megachip() {
+        let disassemble = |instruction| decode_megachip(instruction).unwrap().to_string();
+
+        assert_eq!(disassemble(0x0011), "MEGAON");
+        assert_eq!(disassemble(0x0112), "LDHI I, 0x12....");
+        assert_eq!(disassemble(0x0310), "SPRW 16");
+        assert_eq!(disassemble(0x0600), "DIGISND 0");
+        assert_eq!(disassemble(0x0805), "BMODE 5");
+        assert_eq!(disassemble(0x00e0), "CLS");
+        assert_eq!(disassemble(0x0612), "SYS 0x612");
+    }
 }
@@ -0,0 +1,259 @@
+//! Records displayed frames to an animated GIF and/or a raw RGB dump.
+//!
+//! Timing comes from the emulated frame number passed to `capture`, not from the wall clock, so
+//! recordings play back at the speed the ROM ran at 60 Hz however fast the frontend drew them. A
+//! raw dump holds one `rgb24` image per emulated frame and can be converted with e.g.
+//! `ffmpeg -f rawvideo -pix_fmt rgb24 -s 64x32 -r 60 -i dump.rgb out.mp4`.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Most GIF decoders treat delays under 2 centiseconds as 10, so shorter frames are merged.
+const MIN_GIF_DELAY: u64 = 2;
+/// How hard to work quantising frames with more than 256 colours, from 1 (best) to 30 (fastest).
+const GIF_QUANTISE_SPEED: i32 = 10;
+
+pub struct Recorder<W: Write> {
+    width: usize,
+    height: usize,
+    scale: usize,
+    gif: Option<gif::Encoder<W>>,
+    raw: Option<W>,
+    /// The frame number and most recent buffer of the frame currently being displayed.
+    pending: Option<(u64, Vec<u32>)>,
+    /// The frame number and buffer of the next image to write to the GIF.
+    gif_image: Option<(u64, Vec<u32>)>,
+    /// The frame after the last one written.
+    end: u64,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(width: usize, height: usize, scale: usize) -> Self {
+        Self {
+            width,
+            height,
+            scale: scale.max(1),
+            gif: None,
+            raw: None,
+            pending: None,
+            gif_image: None,
+            end: 0,
+        }
+    }
+
+    /// Writes an animated GIF, scaled up by `scale`, to `out`.
+    pub fn gif(mut self, out: W) -> io::Result<Self> {
+        let mut encoder = gif::Encoder::new(
+            out,
+            (self.width * self.scale) as u16,
+            (self.height * self.scale) as u16,
+            &[],
+        )
+        .map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+        self.gif = Some(encoder);
+        Ok(self)
+    }
+
+    /// Writes one unscaled `rgb24` image per emulated frame to `out`.
+    pub fn raw(mut self, out: W) -> Self {
+        self.raw = Some(out);
+        self
+    }
+
+    /// Captures `buffer` as the contents of the display during emulated frame `frame`.
+    ///
+    /// Later captures for the same frame replace earlier ones.
+    pub fn capture(&mut self, frame: u64, buffer: &[u32]) -> io::Result<()> {
+        match self.pending.take() {
+            Some((start, previous)) if frame > start => {
+                self.write(start, frame, previous)?;
+            }
+            Some((start, _)) => {
+                self.pending = Some((start, buffer.to_vec()));
+                return Ok(());
+            }
+            None => {}
+        }
+        self.pending = Some((frame, buffer.to_vec()));
+        Ok(())
+    }
+
+    /// Writes any remaining frames and the GIF trailer.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some((start, buffer)) = self.pending.take() {
+            self.write(start, start + 1, buffer)?;
+        }
+        if let Some((start, buffer)) = self.gif_image.take() {
+            self.write_gif_image(start, self.end, &buffer)?;
+        }
+        if let Some(encoder) = self.gif.take() {
+            encoder.into_inner()?.flush()?;
+        }
+        if let Some(mut raw) = self.raw.take() {
+            raw.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes `buffer` as the display from frame `start` up to but not including `end`.
+    fn write(&mut self, start: u64, end: u64, buffer: Vec<u32>) -> io::Result<()> {
+        self.end = end;
+
+        if let Some(raw) = self.raw.as_mut() {
+            let rgb: Vec<u8> = buffer
+                .iter()
+                .flat_map(|colour| colour.to_be_bytes()[1..].to_vec())
+                .collect();
+            for _ in start..end {
+                raw.write_all(&rgb)?;
+            }
+        }
+
+        if self.gif.is_some() {
+            match self.gif_image.take() {
+                Some((image_start, image)) if image == buffer => {
+                    self.gif_image = Some((image_start, image));
+                }
+                Some((image_start, _))
+                    if centiseconds(start) - centiseconds(image_start) < MIN_GIF_DELAY =>
+                {
+                    self.gif_image = Some((image_start, buffer));
+                }
+                Some((image_start, image)) => {
+                    self.write_gif_image(image_start, start, &image)?;
+                    self.gif_image = Some((start, buffer));
+                }
+                None => self.gif_image = Some((start, buffer)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes `buffer` as a GIF frame, with a palette of its colours if there are no more than
+    /// 256 of them and quantised to 256 otherwise, e.g. for blended MegaChip frames.
+    fn write_gif_image(&mut self, start: u64, end: u64, buffer: &[u32]) -> io::Result<()> {
+        let (width, height) = (
+            (self.width * self.scale) as u16,
+            (self.height * self.scale) as u16,
+        );
+        let mut indices = HashMap::new();
+        let mut palette = Vec::new();
+        let mut pixels = Vec::with_capacity(buffer.len());
+        for colour in buffer {
+            let index = *indices.entry(*colour).or_insert_with(|| {
+                palette.extend_from_slice(&colour.to_be_bytes()[1..]);
+                palette.len() / 3 - 1
+            });
+            if index > u8::MAX as usize {
+                break;
+            }
+            pixels.push(index as u8);
+        }
+
+        let mut frame = if pixels.len() == buffer.len() {
+            gif::Frame::from_palette_pixels(width, height, self.scale_up(&pixels), palette, None)
+        } else {
+            let rgb: Vec<u8> = self
+                .scale_up(buffer)
+                .iter()
+                .flat_map(|colour| colour.to_be_bytes()[1..].to_vec())
+                .collect();
+            gif::Frame::from_rgb_speed(width, height, &rgb, GIF_QUANTISE_SPEED)
+        };
+        let delay = centiseconds(end) - centiseconds(start);
+        frame.delay = delay.max(MIN_GIF_DELAY) as u16;
+
+        if let Some(encoder) = self.gif.as_mut() {
+            encoder.write_frame(&frame).map_err(gif_error)?;
+        }
+        Ok(())
+    }
+
+    /// Repeats each pixel of `buffer` `scale` times across and down.
+    fn scale_up<T: Copy>(&self, buffer: &[T]) -> Vec<T> {
+        let mut scaled = Vec::with_capacity(buffer.len() * self.scale * self.scale);
+        for row in buffer.chunks(self.width) {
+            let line: Vec<T> = row
+                .iter()
+                .flat_map(|pixel| std::iter::repeat_n(*pixel, self.scale))
+                .collect();
+            for _ in 0..self.scale {
+                scaled.extend_from_slice(&line);
+            }
+        }
+        scaled
+    }
+}
+
+/// The time at which emulated frame `frame` starts, in centiseconds.
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + 30) / 60
+}
+
+fn gif_error(error: gif::EncodingError) -> io::Error {
+    io::Error::other(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gif_timing() {
+        let mut gif = Vec::new();
+        let mut recorder = Recorder::new(2, 1, 1).gif(&mut gif).unwrap();
+        recorder.capture(0, &[0, 0]).unwrap();
+        recorder.capture(0, &[0, 1]).unwrap(); // Replaces the first capture
+        recorder.capture(3, &[1, 1]).unwrap();
+        recorder.capture(4, &[1, 0]).unwrap(); // Shown for 1cs, replaced by the next image
+        recorder.capture(5, &[0, 0]).unwrap();
+        recorder.capture(6, &[0, 0]).unwrap(); // Unchanged, extends the last image
+        recorder.finish().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = decoder.read_info(&gif[..]).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.len()));
+        }
+
+        // Frames 0-3, 3-4 and 4-7 at 60 Hz
+        assert_eq!(frames, vec![(5, 2), (2, 2), (5, 2)]);
+    }
+
+    #[test]
+    fn test_gif_colours() {
+        let mut gif = Vec::new();
+        let mut recorder = Recorder::new(300, 1, 1).gif(&mut gif).unwrap();
+        let few: Vec<u32> = (0..300).map(|x| x % 256 * 0x010101).collect();
+        let many: Vec<u32> = (0..300).map(|x| x * 0x000101).collect();
+        recorder.capture(0, &few).unwrap();
+        recorder.capture(1, &many).unwrap();
+        recorder.finish().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = decoder.read_info(&gif[..]).unwrap();
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(&frame.buffer[299 * 4..], &[43, 43, 43, 0xff]);
+        let frame = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(frame.palette.as_ref().unwrap().len(), 256 * 3);
+        assert_eq!(frame.buffer.len(), 300 * 4);
+    }
+
+    #[test]
+    fn test_raw_repeats_frames() {
+        let mut raw = Vec::new();
+        let mut recorder = Recorder::new(1, 1, 4).raw(&mut raw);
+        recorder.capture(0, &[0x010203]).unwrap();
+        recorder.capture(2, &[0x040506]).unwrap();
+        recorder.finish().unwrap();
+
+        assert_eq!(raw, vec![1, 2, 3, 1, 2, 3, 4, 5, 6]);
+    }
+}
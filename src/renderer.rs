@@ -0,0 +1,359 @@
+//! Maps the pixels of a `FrameBuffer` to `0xRRGGBB` colours for presentation.
+
+use crate::screenshot;
+use crate::vm::FrameBuffer;
+use std::collections::VecDeque;
+use std::io;
+use std::str::FromStr;
+
+/// Colours indexed by the planes a pixel is set in: the background, plane 1, plane 2 and both
+/// planes, as used by XO-CHIP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette(pub [u32; 4]);
+
+pub const PALETTES: [(&str, Palette); 5] = [
+    ("default", Palette([0x22223b, 0x9a8c98, 0x4a4e69, 0xf2e9e4])),
+    ("green", Palette([0x001000, 0x33ff66, 0x119933, 0xaaffcc])),
+    ("amber", Palette([0x1a0f00, 0xffb000, 0x995c00, 0xffd98c])),
+    ("lcd", Palette([0x9bbc0f, 0x0f380f, 0x306230, 0x8bac0f])),
+    (
+        "high-contrast",
+        Palette([0x000000, 0xffffff, 0xffff00, 0x00ffff]),
+    ),
+];
+
+/// The VP-590 colour board's background colours for CHIP-8X: blue, black, green and red.
+const CHIP8X_BACKGROUND: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+
+/// The VP-590 colour board's foreground colours for CHIP-8X: black, red, blue, violet, green,
+/// yellow, aqua and white.
+const CHIP8X_FOREGROUND: [u32; 8] = [
+    0x000000, 0xff0000, 0x0000ff, 0xff00ff, 0x00ff00, 0xffff00, 0x00ffff, 0xffffff,
+];
+
+impl Default for Palette {
+    fn default() -> Self {
+        PALETTES[0].1
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Either the name of a built-in palette or a comma separated list of 2 or 4 hex colours,
+    /// background first, e.g. `000000,ffffff`. With 2 colours, both planes use the second one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, palette)) = PALETTES.iter().find(|(name, _)| *name == s) {
+            return Ok(*palette);
+        }
+        let invalid = || {
+            let names: Vec<_> = PALETTES.iter().map(|(name, _)| *name).collect();
+            format!(
+                "expected a palette name ({}) or 2 or 4 hex colours: {:?}",
+                names.join(", "),
+                s
+            )
+        };
+        if !s.contains(',') {
+            return Err(invalid());
+        }
+
+        let colours = s
+            .split(',')
+            .map(|colour| {
+                let hex = colour.trim().trim_start_matches('#');
+                match u32::from_str_radix(hex, 16) {
+                    Ok(colour) if hex.len() == 6 => Ok(colour),
+                    _ => Err(format!("invalid colour: {:?}", colour)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match colours[..] {
+            [off, on] => Ok(Palette([off, on, on, on])),
+            [off, on, on2, both] => Ok(Palette([off, on, on2, both])),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Reduces the flicker of XOR drawn sprites when presenting frames. Only `Renderer::present`
+/// applies it; `render` and screenshots always show the exact pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Filter {
+    #[default]
+    None,
+    /// Pixels light up immediately and fade out, keeping `decay` of their brightness every frame,
+    /// like CRT phosphor.
+    Phosphor { decay: f32 },
+    /// Pixels stay lit while they're on in any of the last `frames` frames.
+    Persistence { frames: usize },
+}
+
+pub struct Renderer {
+    pub palette: Palette,
+    pub filter: Filter,
+    /// How many times wider than tall pixels are drawn, repeating them across.
+    pub pixel_aspect: usize,
+    /// The brightness of every pixel, for `Filter::Phosphor`.
+    brightness: Vec<f32>,
+    /// The most recent frames, for `Filter::Persistence`.
+    history: VecDeque<FrameBuffer>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            palette: Palette::default(),
+            filter: Filter::None,
+            pixel_aspect: 1,
+            brightness: Vec::new(),
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_pixel_aspect(mut self, pixel_aspect: usize) -> Self {
+        self.pixel_aspect = pixel_aspect.max(1);
+        self
+    }
+
+    /// The width of the rendered frame buffer in pixels, counting widened pixels.
+    pub fn width(&self, frame_buffer: &FrameBuffer) -> usize {
+        frame_buffer.width() * self.pixel_aspect
+    }
+
+    /// Renders the next frame to be displayed, applying the filter.
+    pub fn present(&mut self, frame_buffer: &FrameBuffer) -> Vec<u32> {
+        let (width, height) = (frame_buffer.width(), frame_buffer.height());
+
+        let buffer = match self.filter {
+            Filter::None => return self.render(frame_buffer),
+            // MegaChip double buffers rather than flickering
+            _ if mega_mode(frame_buffer) => return self.render(frame_buffer),
+
+            Filter::Phosphor { decay } => {
+                self.brightness.resize(width * height, 0.0);
+                let mut buffer = Vec::with_capacity(width * height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let (off, on) = self.colours(frame_buffer, x, y);
+                        let brightness = &mut self.brightness[y * width + x];
+                        *brightness = if frame_buffer.pixel(x, y) {
+                            1.0
+                        } else {
+                            *brightness * decay
+                        };
+                        buffer.push(blend(off, on, *brightness));
+                    }
+                }
+                buffer
+            }
+
+            Filter::Persistence { frames } => {
+                self.history.push_front(frame_buffer.clone());
+                self.history.truncate(frames.max(1));
+                let mut buffer = Vec::with_capacity(width * height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let lit = self.history.iter().any(|frame_buffer| {
+                            frame_buffer.width() == width
+                                && frame_buffer.height() == height
+                                && frame_buffer.pixel(x, y)
+                        });
+                        let (off, on) = self.colours(frame_buffer, x, y);
+                        buffer.push(if lit { on } else { off });
+                    }
+                }
+                buffer
+            }
+        };
+        self.widen(buffer)
+    }
+
+    /// Renders the exact pixels of the frame buffer, without the filter.
+    pub fn render(&self, frame_buffer: &FrameBuffer) -> Vec<u32> {
+        if let Some(mega_display) = frame_buffer
+            .mega_display()
+            .filter(|_| mega_mode(frame_buffer))
+        {
+            return self.widen(
+                (0..frame_buffer.height())
+                    .flat_map(|y| (0..frame_buffer.width()).map(move |x| mega_display.colour(x, y)))
+                    .collect(),
+            );
+        }
+
+        let mut buffer = Vec::with_capacity(frame_buffer.width() * frame_buffer.height());
+        for y in 0..frame_buffer.height() {
+            for x in 0..frame_buffer.width() {
+                let (off, on) = self.colours(frame_buffer, x, y);
+                buffer.push(if frame_buffer.pixel(x, y) { on } else { off });
+            }
+        }
+        self.widen(buffer)
+    }
+
+    /// Repeats every pixel `pixel_aspect` times across.
+    fn widen(&self, buffer: Vec<u32>) -> Vec<u32> {
+        if self.pixel_aspect == 1 {
+            return buffer;
+        }
+        buffer
+            .into_iter()
+            .flat_map(|pixel| std::iter::repeat_n(pixel, self.pixel_aspect))
+            .collect()
+    }
+
+    /// The colours of the pixel at `x`, `y` when it's off and on: from the colour zones if the
+    /// frame buffer has them, or else the palette.
+    fn colours(&self, frame_buffer: &FrameBuffer, x: usize, y: usize) -> (u32, u32) {
+        match frame_buffer.colour_zones() {
+            Some(zones) => (
+                CHIP8X_BACKGROUND[zones.background() as usize],
+                CHIP8X_FOREGROUND[zones.foreground(x, y) as usize],
+            ),
+            None => (self.palette.0[0], self.palette.0[1]),
+        }
+    }
+
+    /// Encodes the rendered frame buffer as a PNG, scaling every pixel up to `scale` x `scale`.
+    pub fn to_png(&self, frame_buffer: &FrameBuffer, scale: usize) -> io::Result<Vec<u8>> {
+        screenshot::encode_png(
+            &self.render(frame_buffer),
+            self.width(frame_buffer),
+            frame_buffer.height(),
+            scale,
+        )
+    }
+}
+
+/// Whether the frame buffer shows MegaChip's mega mode display rather than its pixels.
+fn mega_mode(frame_buffer: &FrameBuffer) -> bool {
+    frame_buffer
+        .mega_display()
+        .is_some_and(|mega_display| mega_display.enabled)
+}
+
+/// Mixes `amount` (0.0 to 1.0) of `to` into `from`, channel by channel.
+fn blend(from: u32, to: u32, amount: f32) -> u32 {
+    let from = from.to_be_bytes();
+    let to = to.to_be_bytes();
+    let mut mixed = [0; 4];
+    for channel in 1..4 {
+        let (from, to) = (from[channel] as f32, to[channel] as f32);
+        mixed[channel] = (from + (to - from) * amount).round() as u8;
+    }
+    u32::from_be_bytes(mixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_palette() {
+        assert_eq!("amber".parse::<Palette>().unwrap(), PALETTES[2].1);
+        assert_eq!(
+            "000000,#FFFFFF".parse::<Palette>().unwrap(),
+            Palette([0x000000, 0xffffff, 0xffffff, 0xffffff])
+        );
+        assert_eq!(
+            "000000,ffffff,ff0000,00ff00".parse::<Palette>().unwrap(),
+            Palette([0x000000, 0xffffff, 0xff0000, 0x00ff00])
+        );
+
+        assert!("purple".parse::<Palette>().is_err());
+        assert!("000000".parse::<Palette>().is_err());
+        assert!("000000,fff".parse::<Palette>().is_err());
+        assert!("000000,ffffff,ff0000".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn test_colour_zones() {
+        let mut frame_buffer = FrameBuffer::new(16, 1).with_colour_zones();
+        frame_buffer.toggle_pixel(0, 0);
+        frame_buffer.toggle_pixel(8, 0);
+        let zones = frame_buffer.colour_zones_mut().unwrap();
+        zones.set_foreground(1..2, 0..1, 6);
+        zones.cycle_background();
+
+        let buffer = Renderer::default().render(&frame_buffer);
+        assert_eq!(&buffer[..2], &[0xff0000, 0x000000]);
+        assert_eq!(&buffer[8..10], &[0x00ffff, 0x000000]);
+    }
+
+    #[test]
+    fn test_mega_mode() {
+        let mut frame_buffer = FrameBuffer::new(2, 1).with_mega_display();
+        frame_buffer.toggle_pixel(1, 0);
+        let mega_display = frame_buffer.mega_display_mut().unwrap();
+        mega_display.palette[1] = 0xff0080ff;
+        mega_display.sprite_width = 1;
+        mega_display.draw(0, 0, &[1]);
+        mega_display.flip();
+
+        let renderer = Renderer::new(Palette([0x000000, 0xffffff, 0, 0]));
+        assert_eq!(renderer.render(&frame_buffer), vec![0x000000, 0xffffff]);
+        frame_buffer.mega_display_mut().unwrap().enabled = true;
+        assert_eq!(renderer.render(&frame_buffer), vec![0x0080ff, 0x000000]);
+    }
+
+    #[test]
+    fn test_pixel_aspect() {
+        let mut frame_buffer = FrameBuffer::new(2, 1);
+        frame_buffer.toggle_pixel(1, 0);
+        let palette = Palette([0x000000, 0xffffff, 0, 0]);
+        let mut renderer = Renderer::new(palette)
+            .with_filter(Filter::Persistence { frames: 1 })
+            .with_pixel_aspect(2);
+
+        let widened = vec![0x000000, 0x000000, 0xffffff, 0xffffff];
+        assert_eq!(renderer.render(&frame_buffer), widened);
+        assert_eq!(renderer.present(&frame_buffer), widened);
+
+        let png = renderer.to_png(&frame_buffer, 1).unwrap();
+        let reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (4, 1));
+    }
+
+    #[test]
+    fn test_phosphor() {
+        let palette = Palette([0x000000, 0xc8c8c8, 0, 0]);
+        let mut renderer = Renderer::new(palette).with_filter(Filter::Phosphor { decay: 0.5 });
+        let mut frame_buffer = FrameBuffer::new(1, 1);
+
+        frame_buffer.toggle_pixel(0, 0);
+        assert_eq!(renderer.present(&frame_buffer), vec![0xc8c8c8]);
+        frame_buffer.toggle_pixel(0, 0);
+        assert_eq!(renderer.present(&frame_buffer), vec![0x646464]);
+        assert_eq!(renderer.present(&frame_buffer), vec![0x323232]);
+        assert_eq!(renderer.render(&frame_buffer), vec![0x000000]);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let palette = Palette([0x000000, 0xffffff, 0, 0]);
+        let mut renderer = Renderer::new(palette).with_filter(Filter::Persistence { frames: 2 });
+        let mut frame_buffer = FrameBuffer::new(1, 1);
+
+        frame_buffer.toggle_pixel(0, 0);
+        assert_eq!(renderer.present(&frame_buffer), vec![0xffffff]);
+        frame_buffer.toggle_pixel(0, 0);
+        assert_eq!(renderer.present(&frame_buffer), vec![0xffffff]);
+        assert_eq!(renderer.present(&frame_buffer), vec![0x000000]);
+    }
+}
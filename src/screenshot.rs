@@ -0,0 +1,49 @@
+use std::io;
+
+/// Encodes a buffer of `0xRRGGBB` colours as a PNG, scaling every pixel up to `scale` x `scale`.
+pub fn encode_png(
+    buffer: &[u32],
+    width: usize,
+    height: usize,
+    scale: usize,
+) -> io::Result<Vec<u8>> {
+    let scale = scale.max(1);
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut data = Vec::with_capacity(width * height * scale * scale * 3);
+    for row in buffer.chunks(width) {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|colour| colour.to_be_bytes()[1..].repeat(scale))
+            .collect();
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+    encoder.write_header()?.write_image_data(&data)?;
+
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_png() {
+        let png = encode_png(&[0xff0000, 0x0000ff], 2, 1, 3).unwrap();
+
+        let decoder = png::Decoder::new(&png[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+
+        assert_eq!((reader.info().width, reader.info().height), (6, 3));
+        assert_eq!(&data[0..3], &[0xff, 0x00, 0x00]);
+        assert_eq!(&data[9..12], &[0x00, 0x00, 0xff]);
+        assert_eq!(&data[18..21], &[0xff, 0x00, 0x00]);
+    }
+}
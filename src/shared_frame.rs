@@ -0,0 +1,66 @@
+//! Hands complete frames from the emulator thread to the frontend, once per 60 Hz vblank.
+
+use crate::vm::FrameBuffer;
+use std::sync::Mutex;
+
+pub struct SharedFrame {
+    published: Mutex<Published>,
+}
+
+struct Published {
+    frame: u64,
+    frame_buffer: FrameBuffer,
+    dirty: bool,
+}
+
+impl SharedFrame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            published: Mutex::new(Published {
+                frame: 0,
+                frame_buffer: FrameBuffer::new(width, height),
+                dirty: false,
+            }),
+        }
+    }
+
+    /// Replaces the shared frame buffer with the one at the end of emulated frame `frame`.
+    pub fn publish(&self, frame: u64, frame_buffer: &FrameBuffer) {
+        let mut published = self.published.lock().expect("frame lock poisoned");
+        published.frame = frame;
+        published.frame_buffer.clone_from(frame_buffer);
+        published.dirty = true;
+    }
+
+    /// Copies the shared frame buffer into `frame_buffer` if a frame was published since the last
+    /// call, returning its frame number.
+    pub fn take(&self, frame_buffer: &mut FrameBuffer) -> Option<u64> {
+        let mut published = self.published.lock().expect("frame lock poisoned");
+        if !published.dirty {
+            return None;
+        }
+        frame_buffer.clone_from(&published.frame_buffer);
+        published.dirty = false;
+        Some(published.frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take() {
+        let shared = SharedFrame::new(2, 1);
+        let mut frame_buffer = FrameBuffer::new(2, 1);
+        assert_eq!(shared.take(&mut frame_buffer), None);
+
+        let mut published = FrameBuffer::new(2, 1);
+        published.toggle_pixel(1, 0);
+        shared.publish(3, &published);
+
+        assert_eq!(shared.take(&mut frame_buffer), Some(3));
+        assert!(frame_buffer.pixel(1, 0));
+        assert_eq!(shared.take(&mut frame_buffer), None);
+    }
+}
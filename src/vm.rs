@@ -1,69 +1,405 @@
+mod colour_zones;
 mod cpu;
 mod frame_buffer;
 mod key;
+mod mega_display;
 mod memory;
+mod platform;
 mod registers;
 mod stack;
+pub mod trace;
+pub mod variant;
 
-use crate::opcode::Opcode;
-use crate::vm::frame_buffer::FrameBuffer;
+use crate::opcode::{DecodeError, Opcode};
+pub use crate::vm::colour_zones::ColourZones;
+pub use crate::vm::frame_buffer::FrameBuffer;
 pub use crate::vm::key::Key;
+pub use crate::vm::mega_display::{Blend, MegaDisplay};
 use crate::vm::memory::Memory;
+pub use crate::vm::memory::MEMORY_LENGTH;
+pub use crate::vm::platform::{Platform, PLATFORMS};
 use crate::vm::registers::Registers;
 use crate::vm::stack::Stack;
+use crate::vm::trace::Tracer;
+pub use crate::vm::variant::Variant;
 use rand::prelude::*;
+use std::error::Error;
+use std::fmt;
 use std::sync::mpsc::Receiver;
 
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
+/// What to do when the VM fetches a word that doesn't decode to an instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnUnknown {
+    /// Stop executing; the PC is left on the unknown word.
+    Halt,
+    /// Treat the word as a no-op and carry on.
+    Ignore,
+    /// Hand control back to the caller (e.g. a debugger); the PC is left on the unknown word.
+    Break,
+}
+
+/// Behaviours that differ between CHIP-8 interpreters.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Quirks {
+    /// `DRW` waits for the next vertical blank, as on the COSMAC VIP, limiting drawing to one
+    /// sprite per frame.
+    pub display_wait: bool,
+}
+
+/// Why a ROM couldn't be loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadError {
+    /// The load address is below 0x200, where the interpreter and font live, or past the end of
+    /// memory.
+    InvalidAddress { address: u16, memory_length: usize },
+    /// The ROM doesn't fit in memory above its load address.
+    RomTooLarge {
+        size: usize,
+        address: u16,
+        /// The most bytes that fit at `address`.
+        limit: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::InvalidAddress {
+                address,
+                memory_length,
+            } => write!(
+                f,
+                "invalid load address {:#05x}: programs are loaded from 0x200 up to {:#05x}",
+                address,
+                memory_length - 1
+            ),
+            LoadError::RomTooLarge {
+                size,
+                address,
+                limit,
+            } => write!(
+                f,
+                "ROM too large: {} bytes, but at most {} fit at {:#05x}",
+                size, limit, address
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Running,
+    Halted(DecodeError),
+    Break(DecodeError),
+}
+
+/// A MegaChip sample being played, 8 bit unsigned audio copied out of memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// Where the audio starts.
+    pub address: u32,
+    pub data: Vec<u8>,
+    /// Samples per second.
+    pub rate: u16,
+    pub looping: bool,
+    /// Frames until it finishes, unless it loops.
+    frames_left: u64,
+}
+
+impl Sample {
+    /// Reads the header at `address`: the rate as 16 bits and the length as 24 bits, big-endian,
+    /// with the audio following a byte later. Returns `None` if the header isn't in program
+    /// memory, and cuts the audio short at the end of memory.
+    fn read(memory: &Memory, address: u32, looping: bool) -> Option<Self> {
+        let header = memory.get(address, 6).filter(|header| header.len() == 6)?;
+        let rate = u16::from_be_bytes([header[0], header[1]]);
+        let length = u32::from_be_bytes([0, header[2], header[3], header[4]]);
+        let data = memory
+            .get(address + 6, length as usize)
+            .unwrap_or_default()
+            .to_vec();
+        Some(Self {
+            address: address + 6,
+            frames_left: data.len() as u64 * 60 / rate.max(1) as u64,
+            data,
+            rate,
+            looping,
+        })
+    }
+}
+
 pub struct Vm {
     pub pc: u16,
+    platform: Platform,
+    /// Where `load_rom` puts the ROM, 0x200 unless e.g. 0x600 for ETI-660 programs. Execution
+    /// starts here too, or at `start_address` on platforms that skip a header.
+    pub load_address: u16,
+    pub on_unknown: OnUnknown,
+    pub quirks: Quirks,
+    /// The number of instructions executed per 60 Hz frame.
+    pub cycles_per_frame: usize,
+    cycles: usize,
+    frames: u64,
     stack: Stack,
     memory: Memory,
     registers: Registers,
     st: u8,
     dt: u8,
+    sample: Option<Sample>,
     pub frame_buffer: FrameBuffer,
     rx_key: Receiver<Option<Key>>,
-    rng: ThreadRng,
+    key: Option<Key>,
+    /// CHIP-8X's second keypad, if one is connected.
+    rx_key2: Option<Receiver<Option<Key>>>,
+    key2: Option<Key>,
+    rng: StdRng,
+    tracer: Option<Tracer>,
 }
 
 impl Vm {
     pub fn new(rx_key: Receiver<Option<Key>>) -> Self {
+        let variant = Platform::Chip8.variant();
         Self {
-            pc: 0x200,
+            pc: variant.load_address(),
+            platform: Platform::Chip8,
+            load_address: variant.load_address(),
+            on_unknown: OnUnknown::Halt,
+            quirks: Quirks::default(),
+            cycles_per_frame: 10,
+            cycles: 0,
+            frames: 0,
             stack: Stack::new(),
-            memory: Memory::new(),
+            memory: Memory::new(variant.memory_length(), variant.font()),
             registers: Registers::new(),
             st: 0,
             dt: 0,
-            frame_buffer: FrameBuffer::new(WIDTH, HEIGHT),
+            sample: None,
+            frame_buffer: variant.frame_buffer(),
             rx_key,
-            rng: rand::thread_rng(),
+            key: None,
+            rx_key2: None,
+            key2: None,
+            rng: StdRng::from_entropy(),
+            tracer: None,
         }
     }
 
-    pub fn load_rom(&mut self, rom: &[u8]) {
-        rom.iter().enumerate().for_each(|(address, value)| {
-            self.memory.write(address as u16 + 0x200, *value);
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Switches to `platform`'s load address, memory and display, clearing both.
+    pub fn set_platform(&mut self, platform: Platform) {
+        let variant = platform.variant();
+        self.platform = platform;
+        self.memory = Memory::new(variant.memory_length(), variant.font());
+        self.load_address = variant.load_address();
+        self.pc = self.start_address();
+        self.frame_buffer = variant.frame_buffer();
+    }
+
+    /// Where execution starts, relative to `load_address`.
+    pub fn start_address(&self) -> u16 {
+        self.load_address + self.platform.variant().start_offset()
+    }
+
+    /// Copies `rom` to `load_address` and starts execution at `start_address`.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        let address = self.load_address as usize;
+        if address < 0x200 || address >= self.memory.len() {
+            return Err(LoadError::InvalidAddress {
+                address: self.load_address,
+                memory_length: self.memory.len(),
+            });
+        }
+        let limit = self.memory.len() - address;
+        if rom.len() > limit {
+            return Err(LoadError::RomTooLarge {
+                size: rom.len(),
+                address: self.load_address,
+                limit,
+            });
+        }
+
+        rom.iter().enumerate().for_each(|(offset, value)| {
+            self.memory
+                .write(self.load_address as u32 + offset as u32, *value);
         });
+        self.pc = self.start_address();
+        Ok(())
+    }
+
+    /// Returns the VM to its power-on state, keeping its settings, ready to load a ROM again.
+    pub fn reset(&mut self) {
+        self.pc = self.start_address();
+        self.cycles = 0;
+        self.stack = Stack::new();
+        let variant = self.platform.variant();
+        self.memory = Memory::new(variant.memory_length(), variant.font());
+        self.registers = Registers::new();
+        self.st = 0;
+        self.dt = 0;
+        self.sample = None;
+        self.frame_buffer = variant.frame_buffer();
+        self.key = None;
+        self.key2 = None;
     }
 
     pub fn fetch(&self) -> u16 {
-        (self.memory.read(self.pc) as u16) << 8 | self.memory.read(self.pc + 1) as u16
+        let pc = self.pc as u32;
+        (self.memory.read(pc) as u16) << 8 | self.memory.read(pc + 1) as u16
+    }
+
+    /// Runs until the end of the current frame, or until the VM stops.
+    pub fn frame(&mut self) -> State {
+        let frame = self.frames;
+        while self.frames == frame {
+            match self.step() {
+                State::Running => {}
+                state => return state,
+            }
+        }
+        State::Running
+    }
+
+    /// The number of frames completed.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Fetches, decodes and executes a single instruction.
+    pub fn step(&mut self) -> State {
+        if self.tracer.is_some() {
+            let (state, entry) = self.step_traced();
+            if let (Some(tracer), Some(entry)) = (self.tracer.as_mut(), entry) {
+                tracer.write(&entry).expect("unable to write trace");
+            }
+            return state;
+        }
+
+        match self.platform.variant().decode(self.fetch()) {
+            Ok(opcode) => {
+                self.execute(opcode);
+                State::Running
+            }
+            Err(error) => self.unknown(error),
+        }
+    }
+
+    /// Like `step`, but also returns a trace entry if an instruction was executed.
+    pub fn step_traced(&mut self) -> (State, Option<trace::Entry>) {
+        let pc = self.pc;
+        let instruction = self.fetch();
+        let opcode = match self.platform.variant().decode(instruction) {
+            Ok(opcode) => opcode,
+            Err(error) => return (self.unknown(error), None),
+        };
+        let mnemonic = opcode.to_string();
+        let registers = self.registers.clone();
+        self.memory.take_writes();
+
+        self.execute(opcode);
+
+        let entry = trace::Entry {
+            pc,
+            instruction,
+            mnemonic,
+            changed: trace::changed_registers(&registers, &self.registers),
+            i: self.registers.i,
+            dt: self.dt,
+            st: self.st,
+            writes: self.memory.take_writes(),
+        };
+        (State::Running, Some(entry))
+    }
+
+    fn unknown(&mut self, error: DecodeError) -> State {
+        match self.on_unknown {
+            OnUnknown::Halt => State::Halted(error),
+            OnUnknown::Ignore => {
+                cpu::skip(self);
+                self.tick();
+                State::Running
+            }
+            OnUnknown::Break => State::Break(error),
+        }
     }
 
     pub fn execute(&mut self, opcode: Opcode) {
-        cpu::execute(self, opcode)
+        let display_wait = self.quirks.display_wait && matches!(opcode, Opcode::DRW(..));
+
+        if let Some(opcode) = self.platform.variant().execute(self, opcode) {
+            cpu::execute(self, opcode);
+        }
+        self.tick();
+
+        if display_wait && self.cycles != 0 {
+            self.end_frame();
+        }
+    }
+
+    /// Counts an executed instruction, ending the frame after `cycles_per_frame` of them.
+    fn tick(&mut self) {
+        self.cycles += 1;
+        if self.cycles >= self.cycles_per_frame {
+            self.end_frame();
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.cycles = 0;
+        self.frames += 1;
+        self.update_timers();
+    }
+
+    /// Writes an entry to `tracer` for every instruction executed by `step`.
+    pub fn trace(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Seeds the random number generator used by `RND`, making runs repeatable.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
     pub fn rand(&mut self) -> u8 {
         self.rng.gen()
     }
 
+    /// The key currently held down, as last sent over `rx_key`.
     pub fn try_key(&mut self) -> Option<Key> {
-        self.rx_key.try_iter().last().unwrap_or(None)
+        if let Some(key) = self.rx_key.try_iter().last() {
+            self.key = key;
+        }
+        self.key
+    }
+
+    /// Connects CHIP-8X's second keypad, whose keys are sent over `rx_key2`.
+    pub fn connect_keypad2(&mut self, rx_key2: Receiver<Option<Key>>) {
+        self.rx_key2 = Some(rx_key2);
+    }
+
+    /// The key currently held down on the second keypad, if it's connected.
+    pub fn try_key2(&mut self) -> Option<Key> {
+        if let Some(key) = self.rx_key2.as_ref().and_then(|rx| rx.try_iter().last()) {
+            self.key2 = key;
+        }
+        self.key2
+    }
+
+    /// Whether the sound timer is running or a sample playing, i.e. the buzzer should sound.
+    pub fn sound(&self) -> bool {
+        self.st > 0 || self.sample.is_some()
+    }
+
+    /// The MegaChip sample being played, if any.
+    pub fn sample(&self) -> Option<&Sample> {
+        self.sample.as_ref()
     }
 
     pub fn update_timers(&mut self) {
@@ -73,5 +409,249 @@ impl Vm {
         if self.dt > 0 {
             self.dt -= 1;
         }
+        if let Some(sample) = self.sample.as_mut().filter(|sample| !sample.looping) {
+            sample.frames_left = sample.frames_left.saturating_sub(1);
+            if sample.frames_left == 0 {
+                self.sample = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcode::DataRegister;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_display_wait() {
+        // DRW V0, V0, 1; JP 0x200
+        let rom = [0xd0, 0x01, 0x12, 0x00];
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.load_rom(&rom).unwrap();
+
+        vm.frame();
+        assert_eq!(vm.pc, 0x200);
+
+        vm.quirks.display_wait = true;
+        vm.frame();
+        assert_eq!(vm.pc, 0x202);
+        vm.frame();
+        assert_eq!(vm.pc, 0x202);
+        assert_eq!(vm.frames(), 3);
+    }
+
+    #[test]
+    fn test_load_rom() {
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.load_address = 0x600;
+        vm.load_rom(&[0x12, 0x34]).unwrap();
+        assert_eq!(vm.pc, 0x600);
+        assert_eq!(vm.fetch(), 0x1234);
+
+        assert!(vm.load_rom(&[0; 0xfff - 0x600]).is_ok());
+        let error = vm.load_rom(&[0; 0xfff - 0x600 + 1]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ROM too large: 2560 bytes, but at most 2559 fit at 0x600"
+        );
+
+        for address in [0x000, 0x100, 0x1ff, 0xfff, 0x1000] {
+            vm.load_address = address;
+            let error = vm.load_rom(&[0x12, 0x34]).unwrap_err();
+            assert!(
+                matches!(error, LoadError::InvalidAddress { .. }),
+                "{:#x}",
+                address
+            );
+        }
+        vm.load_address = 0x100;
+        assert_eq!(
+            vm.load_rom(&[]).unwrap_err().to_string(),
+            "invalid load address 0x100: programs are loaded from 0x200 up to 0xffe"
+        );
+    }
+
+    #[test]
+    fn test_platform() {
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.set_platform(Platform::Eti660);
+        vm.load_rom(&[0x12, 0x34]).unwrap();
+        assert_eq!(vm.pc, 0x600);
+        assert_eq!(vm.frame_buffer.width(), 64);
+        assert_eq!(vm.frame_buffer.height(), 48);
+
+        // DRW V0, V0, 1; HCLS, past the header
+        let mut rom = vec![0; 0xc0];
+        rom.extend_from_slice(&[0xd0, 0x01, 0x02, 0x30]);
+        vm.set_platform(Platform::HiRes);
+        vm.load_rom(&rom).unwrap();
+        assert_eq!(vm.pc, 0x2c0);
+        assert_eq!(vm.frame_buffer.height(), 64);
+        vm.step();
+        assert!(vm.frame_buffer.pixel(0, 0));
+        vm.step();
+        assert!(!vm.frame_buffer.pixel(0, 0));
+
+        assert_eq!("chip-8-hires".parse(), Ok(Platform::HiRes));
+        assert_eq!(Platform::HiRes.to_string(), "chip-8-hires");
+        assert!("schip".parse::<Platform>().is_err());
+    }
+
+    #[test]
+    fn test_chip8x() {
+        // LD V0, 0x10; LD V1, 0x00; LD V2, 5; COL V0, V2, 0; BGC; LD V3, 0x75; LD V4, 0x13;
+        // ADDN V3, V4; SKNP2 V0
+        let rom = [
+            0x60, 0x10, 0x61, 0x00, 0x62, 0x05, 0xb0, 0x20, 0x02, 0xa0, 0x63, 0x75, 0x64, 0x13,
+            0x53, 0x41, 0xe0, 0xf5,
+        ];
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.set_platform(Platform::Chip8X);
+        vm.load_rom(&rom).unwrap();
+        assert_eq!(vm.pc, 0x300);
+        for _ in 0..9 {
+            vm.step();
+        }
+
+        let zones = vm.frame_buffer.colour_zones().unwrap();
+        assert_eq!(zones.foreground(8, 3), 5);
+        assert_eq!(zones.foreground(8, 4), 1);
+        assert_eq!(zones.foreground(16, 0), 1);
+        assert_eq!(zones.background(), 1);
+        assert_eq!(vm.registers.read(DataRegister::V3), 0x00);
+        assert_eq!(vm.pc, 0x314);
+
+        // SKP2 V0; SKNP2 V0, with 0 held on the second keypad
+        let (tx_key2, rx_key2) = mpsc::channel();
+        vm.connect_keypad2(rx_key2);
+        vm.reset();
+        assert_eq!(vm.frame_buffer.colour_zones().unwrap().background(), 0);
+        vm.load_rom(&[0xe0, 0xf2, 0x00, 0x00, 0xe0, 0xf5]).unwrap();
+        tx_key2.send(Some(Key::Key0)).unwrap();
+        vm.step();
+        assert_eq!(vm.pc, 0x304);
+        vm.step();
+        assert_eq!(vm.pc, 0x306);
+        assert_eq!(vm.try_key(), None);
+    }
+
+    #[test]
+    fn test_megachip() {
+        // MEGAON; LDHI I, 0x010000; LDPAL 1; LDHI I, 0x010004; SPRW 2; SPRH 1; DRW V0, V0, 0;
+        // CLS; LDHI I, 0x010006; DIGISND 1
+        let rom = [
+            0x00, 0x11, 0x01, 0x01, 0x00, 0x00, 0x02, 0x01, 0x01, 0x01, 0x00, 0x04, 0x03, 0x02,
+            0x04, 0x01, 0xd0, 0x00, 0x00, 0xe0, 0x01, 0x01, 0x00, 0x06, 0x06, 0x01,
+        ];
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.set_platform(Platform::MegaChip);
+        vm.load_rom(&rom).unwrap();
+        // A palette entry, a sprite of colour 1 and a transparent pixel, then the header of a
+        // 60 Hz sample 30 bytes long
+        let data = [
+            0xff, 0x00, 0x80, 0xff, 0x01, 0x00, 0x00, 0x3c, 0x00, 0x00, 0x1e,
+        ];
+        for (offset, byte) in data.iter().enumerate() {
+            vm.memory.write(0x10000 + offset as u32, *byte);
+        }
+        for _ in 0..10 {
+            vm.step();
+        }
+
+        assert_eq!(vm.registers.i, 0x10006);
+        assert_eq!(vm.pc, 0x21a);
+        let mega_display = vm.frame_buffer.mega_display().unwrap();
+        assert_eq!(mega_display.palette[1], 0xff0080ff);
+        assert_eq!(mega_display.colour(0, 0), 0x0080ff);
+        assert_eq!(mega_display.colour(1, 0), 0x000000);
+        assert_eq!(vm.frame_buffer.width(), 256);
+
+        assert_eq!(vm.sample().unwrap().data.len(), 30);
+        assert!(vm.sound());
+        for _ in 0..30 {
+            vm.update_timers();
+        }
+        assert!(!vm.sound());
+    }
+
+    #[test]
+    fn test_sample_bounds() {
+        // LD I, 0x100; DIGISND 1; LDHI I, 0xfffffe; DIGISND 1; LDHI I, 0xfffff0; DIGISND 0
+        let rom = [
+            0xa1, 0x00, 0x06, 0x01, 0x01, 0xff, 0xff, 0xfe, 0x06, 0x01, 0x01, 0xff, 0xff, 0xf0,
+            0x06, 0x00,
+        ];
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.set_platform(Platform::MegaChip);
+        vm.load_rom(&rom).unwrap();
+        // A header claiming more audio than is left in memory
+        for (offset, byte) in [0x1f, 0x40, 0xff, 0xff, 0xff, 0x00].iter().enumerate() {
+            vm.memory.write(0xfffff0 + offset as u32, *byte);
+        }
+
+        vm.step();
+        vm.step();
+        assert_eq!(vm.sample(), None);
+        vm.step();
+        vm.step();
+        assert_eq!(vm.sample(), None);
+        vm.step();
+        vm.step();
+        assert_eq!(vm.sample().unwrap().data.len(), 10);
+    }
+
+    #[test]
+    fn test_mega_bounds() {
+        // MEGAON; SPRW 0; SPRH 0; LDHI I, 0xffff00; DRW V0, V0, 0; LDPAL 0x80
+        let rom = [
+            0x00, 0x11, 0x03, 0x00, 0x04, 0x00, 0x01, 0xff, 0xff, 0x00, 0xd0, 0x00, 0x02, 0x80,
+        ];
+        let (_tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.set_platform(Platform::MegaChip);
+        vm.load_rom(&rom).unwrap();
+        vm.memory.write(0xffff00, 1);
+        vm.memory.write(0xfffffc, 0xff);
+        vm.memory.write(0xfffffd, 0x12);
+
+        for _ in 0..6 {
+            assert_eq!(vm.step(), State::Running);
+        }
+        let mega_display = vm.frame_buffer.mega_display().unwrap();
+        assert_eq!(mega_display.palette[0x40], 0xff120000);
+        assert_eq!(mega_display.palette[0x41], 0);
+    }
+
+    #[test]
+    fn test_reset() {
+        // LD V0, 0x10; LD ST, V0; DRW V0, V0, 1; CALL 0x200
+        let rom = [0x60, 0x10, 0xf0, 0x18, 0xd0, 0x01, 0x22, 0x00];
+        let (tx_key, rx_key) = mpsc::channel();
+        let mut vm = Vm::new(rx_key);
+        vm.load_rom(&rom).unwrap();
+        tx_key.send(Some(Key::Key5)).unwrap();
+        assert_eq!(vm.try_key(), Some(Key::Key5));
+        for _ in 0..5 {
+            vm.step();
+        }
+        assert!(vm.sound());
+        assert_eq!(vm.stack.pop(), Ok(0x208));
+
+        vm.reset();
+        assert_eq!(vm.pc, 0x200);
+        assert!(!vm.sound());
+        assert!(vm.stack.pop().is_err());
+        assert_eq!(vm.registers.read(DataRegister::V0), 0);
+        assert!(vm.frame_buffer == FrameBuffer::new(WIDTH, HEIGHT));
+        assert_eq!(vm.fetch(), 0);
+        assert_eq!(vm.try_key(), None);
     }
 }
@@ -0,0 +1,73 @@
+use std::ops::Range;
+
+/// The colours CHIP-8X sets through the VP-590 colour board: one background colour for the whole
+/// display and a foreground colour for every zone 8 pixels wide and 1 row tall.
+#[derive(Clone, PartialEq)]
+pub struct ColourZones {
+    background: u8,
+    foreground: Vec<u8>,
+    columns: usize,
+    rows: usize,
+}
+
+/// The foreground colour zones start out with, red.
+const INITIAL_FOREGROUND: u8 = 1;
+
+impl ColourZones {
+    pub fn new(width: usize, height: usize) -> Self {
+        let columns = width.div_ceil(8);
+        Self {
+            background: 0,
+            foreground: vec![INITIAL_FOREGROUND; columns * height],
+            columns,
+            rows: height,
+        }
+    }
+
+    /// The background colour, 0 to 3: blue, black, green or red.
+    pub fn background(&self) -> u8 {
+        self.background
+    }
+
+    /// Moves on to the next background colour, wrapping back to the first.
+    pub fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % 4;
+    }
+
+    /// The foreground colour of the pixel at `x`, `y`, 0 to 7: black, red, blue, violet, green,
+    /// yellow, aqua or white.
+    pub fn foreground(&self, x: usize, y: usize) -> u8 {
+        self.foreground[y * self.columns + x / 8]
+    }
+
+    /// Colours the zones in `columns` (of 8 pixels) and pixel `rows`, skipping any off the
+    /// display. Only the low 3 bits of `colour` are used.
+    pub fn set_foreground(&mut self, columns: Range<usize>, rows: Range<usize>, colour: u8) {
+        let columns = columns.start.min(self.columns)..columns.end.min(self.columns);
+        for row in rows.start.min(self.rows)..rows.end.min(self.rows) {
+            for column in columns.clone() {
+                self.foreground[row * self.columns + column] = colour & 7;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_foreground() {
+        let mut zones = ColourZones::new(64, 32);
+        zones.set_foreground(1..3, 30..34, 0xf4);
+        assert_eq!(zones.foreground(8, 30), 4);
+        assert_eq!(zones.foreground(23, 31), 4);
+        assert_eq!(zones.foreground(24, 31), INITIAL_FOREGROUND);
+        assert_eq!(zones.foreground(8, 29), INITIAL_FOREGROUND);
+
+        for _ in 0..5 {
+            zones.cycle_background();
+        }
+        assert_eq!(zones.background(), 1);
+    }
+}
@@ -1,12 +1,11 @@
 use crate::opcode::{DataRegister, Kk, Nnn, Opcode, N};
 use crate::vm::memory::MEMORY_LENGTH;
-use crate::vm::{Vm, HEIGHT, WIDTH};
+use crate::vm::Vm;
 
+/// Executes an instruction of the CHIP-8 instruction set shared by every variant.
 pub fn execute(vm: &mut Vm, opcode: Opcode) {
     let mut new_pc = vm.pc + 2;
 
-    vm.update_timers();
-
     match opcode {
         Opcode::DisplayClear => vm.frame_buffer.clear(),
 
@@ -48,29 +47,28 @@ pub fn execute(vm: &mut Vm, opcode: Opcode) {
             vm.memory.write(vm.registers.i + 2, parts.ones);
         }
 
-        Opcode::LDI(Nnn(value)) => vm.registers.i = value,
+        Opcode::LDI(Nnn(value)) => vm.registers.i = value as u32,
 
         Opcode::LdAllI(x) => {
             for r in 0..=x as u8 {
                 let value = vm.registers.read(DataRegister::from(r));
-                vm.memory.write(vm.registers.i + r as u16, value);
+                vm.memory.write(vm.registers.i + r as u32, value);
             }
         }
 
         Opcode::LdAll(x) => {
             for r in 0..=x as u8 {
-                let value = vm.memory.read(vm.registers.i + r as u16);
+                let value = vm.memory.read(vm.registers.i + r as u32);
                 vm.registers.write(DataRegister::from(r), value);
             }
         }
 
-        Opcode::LdF(x) => vm.registers.i = vm.registers.read(x) as u16 * 5,
+        Opcode::LdF(x) => vm.registers.i = vm.registers.read(x) as u32 * 5,
 
-        Opcode::LdKey(x) => loop {
-            if let Some(key) = vm.try_key() {
-                vm.registers.write(x, key as u8);
-                break;
-            }
+        Opcode::LdKey(x) => match vm.try_key() {
+            Some(key) => vm.registers.write(x, key as u8),
+            // Wait for a key by executing this instruction again
+            None => new_pc = vm.pc,
         },
 
         Opcode::LdSt(x) => vm.st = vm.registers.read(x),
@@ -84,46 +82,31 @@ pub fn execute(vm: &mut Vm, opcode: Opcode) {
             vm.registers.write(x, y);
         }
 
-        Opcode::SE3(register, Kk(value)) => {
-            if vm.registers.read(register) == value {
-                new_pc += 2
-            }
-        }
+        Opcode::SE3(register, Kk(value)) if vm.registers.read(register) == value => new_pc += 2,
 
-        Opcode::SNE4(register, Kk(value)) => {
-            if vm.registers.read(register) != value {
-                new_pc += 2
-            }
-        }
+        Opcode::SNE4(register, Kk(value)) if vm.registers.read(register) != value => new_pc += 2,
 
-        Opcode::SE5(x, y) => {
-            if vm.registers.read(x) == vm.registers.read(y) {
-                new_pc += 2
-            }
-        }
+        Opcode::SE5(x, y) if vm.registers.read(x) == vm.registers.read(y) => new_pc += 2,
 
-        Opcode::SNE(x, y) => {
-            if vm.registers.read(x) != vm.registers.read(y) {
-                new_pc += 2
-            }
-        }
+        Opcode::SNE(x, y) if vm.registers.read(x) != vm.registers.read(y) => new_pc += 2,
 
         Opcode::DRW(x_register, y_register, N(height)) => {
             let x_offset = vm.registers.read(x_register);
             let y_offset = vm.registers.read(y_register);
 
+            let (width, screen_height) = (vm.frame_buffer.width(), vm.frame_buffer.height());
             let mut vf = 0;
 
             for y in 0..height {
-                let line = vm.memory.read(vm.registers.i + y as u16);
+                let line = vm.memory.read(vm.registers.i + y as u32);
                 for x in 0..8 {
                     if (line & (0x80 >> x)) != 0 {
                         let mut x = x_offset as usize + x as usize;
-                        if x >= WIDTH {
+                        if x >= width {
                             x = 0;
                         }
                         let mut y = y_offset as usize + y as usize;
-                        if y >= HEIGHT {
+                        if y >= screen_height {
                             y = 0;
                         }
 
@@ -151,7 +134,8 @@ pub fn execute(vm: &mut Vm, opcode: Opcode) {
         }
 
         Opcode::AddI(x) => {
-            vm.registers.i = vm.registers.i.wrapping_add(vm.registers.read(x) as u16);
+            // I is 16 bits wide
+            vm.registers.i = (vm.registers.i + vm.registers.read(x) as u32) & 0xffff;
         }
 
         Opcode::SUB8(x, y) => {
@@ -199,11 +183,24 @@ pub fn execute(vm: &mut Vm, opcode: Opcode) {
             let rand = vm.rand();
             vm.registers.write(x, rand & kk);
         }
+
+        // Skips not taken, and the opcodes variants add, which they execute themselves
+        _ => {}
     }
 
     vm.pc = wrap_pc(new_pc);
 }
 
+/// Moves past the current instruction and the `skipped` after it, for variants' own opcodes.
+pub fn advance(vm: &mut Vm, skipped: u16) {
+    vm.pc = wrap_pc(vm.pc + 2 + skipped * 2);
+}
+
+/// Steps over the current instruction without executing it.
+pub fn skip(vm: &mut Vm) {
+    vm.pc = wrap_pc(vm.pc + 2);
+}
+
 fn wrap_pc(pc: u16) -> u16 {
     pc & (MEMORY_LENGTH as u16 - 1)
 }
@@ -227,7 +224,7 @@ impl Parts {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::vm::memory::Memory;
+    use crate::vm::memory::{Memory, FONT};
 
     #[test]
     fn test_wrap_pc() {
@@ -239,14 +236,14 @@ mod tests {
         assert_eq!(wrap_pc(memory_length + 1), 0);
         assert_eq!(wrap_pc(memory_length + 2), 0);
 
-        let memory = Memory::new();
+        let memory = Memory::new(MEMORY_LENGTH, &FONT);
 
         // No panics
-        memory.read(wrap_pc(0));
-        memory.read(wrap_pc(42));
-        memory.read(wrap_pc(memory_length));
-        memory.read(wrap_pc(memory_length + 1));
-        memory.read(wrap_pc(memory_length + 2));
+        memory.read(u32::from(wrap_pc(0)));
+        memory.read(u32::from(wrap_pc(42)));
+        memory.read(u32::from(wrap_pc(memory_length)));
+        memory.read(u32::from(wrap_pc(memory_length + 1)));
+        memory.read(u32::from(wrap_pc(memory_length + 2)));
     }
 
     #[test]
@@ -1,41 +1,166 @@
-const ON: u32 = 0x9a8c98;
-const OFF: u32 = 0x22223b;
+use crate::vm::{ColourZones, MegaDisplay};
+use sha1::{Digest, Sha1};
 
+/// A monochrome display, one bit per pixel, packed 8 pixels to a byte with each row starting on a
+/// new byte. Mapping pixels to colours is left to `Renderer`, which uses the colour zones
+/// instead of its palette on CHIP-8X and shows the mega mode display instead on MegaChip.
+#[derive(Clone, PartialEq)]
 pub struct FrameBuffer {
-    buffer: Vec<u32>,
+    pixels: Vec<u8>,
     width: usize,
     height: usize,
+    colour_zones: Option<ColourZones>,
+    mega_display: Option<MegaDisplay>,
 }
 
 impl FrameBuffer {
     pub fn new(width: usize, height: usize) -> Self {
         Self {
-            buffer: vec![OFF; width * height],
+            pixels: vec![0; width.div_ceil(8) * height],
             width,
             height,
+            colour_zones: None,
+            mega_display: None,
         }
     }
 
+    /// Adds CHIP-8X colour zones over the pixels.
+    pub fn with_colour_zones(mut self) -> Self {
+        self.colour_zones = Some(ColourZones::new(self.width, self.height));
+        self
+    }
+
+    pub fn colour_zones(&self) -> Option<&ColourZones> {
+        self.colour_zones.as_ref()
+    }
+
+    pub fn colour_zones_mut(&mut self) -> Option<&mut ColourZones> {
+        self.colour_zones.as_mut()
+    }
+
+    /// Adds a MegaChip mega mode display the same size as the pixels.
+    pub fn with_mega_display(mut self) -> Self {
+        self.mega_display = Some(MegaDisplay::new(self.width, self.height));
+        self
+    }
+
+    pub fn mega_display(&self) -> Option<&MegaDisplay> {
+        self.mega_display.as_ref()
+    }
+
+    pub fn mega_display_mut(&mut self) -> Option<&mut MegaDisplay> {
+        self.mega_display.as_mut()
+    }
+
+    /// Flips a pixel, returning `true` if it was on (a collision).
     pub fn toggle_pixel(&mut self, x: usize, y: usize) -> bool {
-        let l = y * self.width + x;
-        assert!(l < self.width * self.height);
-
-        if self.buffer[l] == ON {
-            self.buffer[l] = OFF;
-            true
-        } else {
-            self.buffer[l] = ON;
-            false
+        assert!(x < self.width && y < self.height);
+        let (byte, mask) = self.index(x, y);
+
+        let collision = self.pixels[byte] & mask != 0;
+        self.pixels[byte] ^= mask;
+        collision
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        let (byte, mask) = self.index(x, y);
+        self.pixels[byte] & mask != 0
+    }
+
+    fn index(&self, x: usize, y: usize) -> (usize, u8) {
+        (y * self.width.div_ceil(8) + x / 8, 0x80 >> (x % 8))
+    }
+
+    /// One line per row, with `#` for pixels that are on and `.` for pixels that are off.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width + 1) * self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                ascii.push(if self.pixel(x, y) { '#' } else { '.' });
+            }
+            ascii.push('\n');
         }
+        ascii
     }
 
-    pub fn buffer(&self) -> &Vec<u32> {
-        &self.buffer
+    /// One line per two rows, using Unicode half blocks so pixels come out roughly square in a
+    /// terminal.
+    pub fn to_half_blocks(&self) -> Vec<String> {
+        (0..self.height)
+            .step_by(2)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        let bottom = y + 1 < self.height && self.pixel(x, y + 1);
+                        match (self.pixel(x, y), bottom) {
+                            (false, false) => ' ',
+                            (true, false) => '▀',
+                            (false, true) => '▄',
+                            (true, true) => '█',
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
     }
 
+    /// SHA-1 of one byte (0 or 1) per pixel, row by row, as lowercase hex.
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha1::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                hasher.update([self.pixel(x, y) as u8]);
+            }
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Turns every pixel off, leaving the colour zones as they are.
     pub fn clear(&mut self) {
-        for i in 0..(self.width * self.height) {
-            self.buffer[i] = OFF;
+        for byte in self.pixels.iter_mut() {
+            *byte = 0;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_pixel() {
+        let mut frame_buffer = FrameBuffer::new(12, 2);
+
+        assert!(!frame_buffer.toggle_pixel(9, 1));
+        assert!(frame_buffer.pixel(9, 1));
+        assert!(!frame_buffer.pixel(9, 0));
+        assert!(!frame_buffer.pixel(1, 1));
+
+        assert!(frame_buffer.toggle_pixel(9, 1));
+        assert!(!frame_buffer.pixel(9, 1));
+    }
+
+    #[test]
+    fn test_to_half_blocks() {
+        let mut frame_buffer = FrameBuffer::new(4, 3);
+        frame_buffer.toggle_pixel(1, 0);
+        frame_buffer.toggle_pixel(2, 1);
+        frame_buffer.toggle_pixel(3, 0);
+        frame_buffer.toggle_pixel(3, 1);
+        frame_buffer.toggle_pixel(0, 2);
+
+        assert_eq!(frame_buffer.to_half_blocks(), vec![" ▀▄█", "▀   "]);
+    }
+}
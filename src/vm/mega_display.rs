@@ -0,0 +1,164 @@
+/// How MegaChip sprites are mixed into what's already on the display.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Blend {
+    #[default]
+    Normal,
+    /// Mixes in 25%, 50% or 75% of the sprite.
+    Percent25,
+    Percent50,
+    Percent75,
+    Add,
+    Multiply,
+}
+
+impl Blend {
+    /// The mode set by `BMODE n`, falling back to normal for unknown modes.
+    pub fn from(n: u8) -> Self {
+        match n {
+            1 => Blend::Percent25,
+            2 => Blend::Percent50,
+            3 => Blend::Percent75,
+            4 => Blend::Add,
+            5 => Blend::Multiply,
+            _ => Blend::Normal,
+        }
+    }
+
+    fn apply(self, under: u32, over: u32) -> u32 {
+        let mix = |amount: u32| {
+            channels(under, over, |under, over| {
+                (under * (4 - amount) + over * amount) / 4
+            })
+        };
+        match self {
+            Blend::Normal => over,
+            Blend::Percent25 => mix(1),
+            Blend::Percent50 => mix(2),
+            Blend::Percent75 => mix(3),
+            Blend::Add => channels(under, over, |under, over| (under + over).min(0xff)),
+            Blend::Multiply => channels(under, over, |under, over| under * over / 0xff),
+        }
+    }
+}
+
+/// Combines `a` and `b` colour channel by colour channel, leaving the result opaque.
+fn channels(a: u32, b: u32, combine: impl Fn(u32, u32) -> u32) -> u32 {
+    let (a, b) = (a.to_be_bytes(), b.to_be_bytes());
+    let mut combined = [0xff; 4];
+    for channel in 1..4 {
+        combined[channel] = combine(a[channel] as u32, b[channel] as u32) as u8;
+    }
+    u32::from_be_bytes(combined)
+}
+
+/// The 256 colour display of MegaChip's mega mode. Sprites are drawn to a back buffer, which is
+/// only shown when the program clears the screen to start the next frame.
+#[derive(Clone, PartialEq)]
+pub struct MegaDisplay {
+    /// Whether mega mode is on. Until it is, programs draw monochrome pixels as on CHIP-8.
+    pub enabled: bool,
+    /// `0xAARRGGBB` colours by index. Index 0 is transparent in sprites.
+    pub palette: [u32; 256],
+    /// The size of sprites in pixels, one byte per pixel.
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    /// The opacity of the whole display.
+    pub alpha: u8,
+    pub blend: Blend,
+    /// Drawing over a pixel of this colour index is a collision. Index 0, where nothing's been
+    /// drawn, never collides.
+    pub collision_colour: u8,
+    width: usize,
+    height: usize,
+    /// The colour and colour index of every pixel being drawn.
+    back: Vec<u32>,
+    indices: Vec<u8>,
+    /// The colours last shown.
+    front: Vec<u32>,
+}
+
+impl MegaDisplay {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            enabled: false,
+            palette: [0; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xff,
+            blend: Blend::Normal,
+            collision_colour: 0,
+            width,
+            height,
+            back: vec![0; width * height],
+            indices: vec![0; width * height],
+            front: vec![0; width * height],
+        }
+    }
+
+    /// Draws `sprite`, `sprite_width` colour indices to a row, with its top left at `x`, `y`,
+    /// clipping it at the edges. Returns `true` if it was drawn over the collision colour.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+        for (row, line) in sprite.chunks(self.sprite_width.max(1)).enumerate() {
+            for (column, index) in line.iter().enumerate() {
+                let (x, y) = (x + column, y + row);
+                if *index == 0 || x >= self.width || y >= self.height {
+                    continue;
+                }
+                let pixel = y * self.width + x;
+                collision |=
+                    self.indices[pixel] != 0 && self.indices[pixel] == self.collision_colour;
+                self.indices[pixel] = *index;
+                self.back[pixel] = self
+                    .blend
+                    .apply(self.back[pixel], self.palette[*index as usize]);
+            }
+        }
+        collision
+    }
+
+    /// Shows what's been drawn and clears the back buffer for the next frame.
+    pub fn flip(&mut self) {
+        self.front.clone_from(&self.back);
+        self.back.fill(0);
+        self.indices.fill(0);
+    }
+
+    /// The `0xRRGGBB` colour shown for the pixel at `x`, `y`, faded by its and the display's
+    /// alpha.
+    pub fn colour(&self, x: usize, y: usize) -> u32 {
+        let [alpha, red, green, blue] = self.front[y * self.width + x].to_be_bytes();
+        let opacity = alpha as u32 * self.alpha as u32;
+        let fade = |channel: u8| (channel as u32 * opacity / (0xff * 0xff)) as u8;
+        u32::from_be_bytes([0, fade(red), fade(green), fade(blue)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        let mut display = MegaDisplay::new(4, 2);
+        display.palette[1] = 0xffff0000;
+        display.palette[2] = 0xff0000ff;
+        display.sprite_width = 2;
+        display.collision_colour = 1;
+
+        assert!(!display.draw(0, 0, &[1, 0, 1, 1]));
+        display.blend = Blend::from(2);
+        assert!(display.draw(1, 1, &[2, 2, 2, 2]));
+        assert_eq!(display.colour(0, 0), 0x000000);
+
+        display.flip();
+        assert_eq!(display.colour(0, 0), 0xff0000);
+        assert_eq!(display.colour(1, 0), 0x000000);
+        assert_eq!(display.colour(0, 1), 0xff0000);
+        assert_eq!(display.colour(1, 1), 0x7f007f);
+        assert_eq!(display.colour(2, 1), 0x00007f);
+
+        display.alpha = 0x80;
+        assert_eq!(display.colour(0, 0), 0x800000);
+    }
+}
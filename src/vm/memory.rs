@@ -1,4 +1,5 @@
 pub const MEMORY_LENGTH: usize = 0xfff;
+/// The font most interpreters use.
 pub const FONT: [u8; 80] = [
     0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -18,19 +19,50 @@ pub const FONT: [u8; 80] = [
     0xf0, 0x80, 0xf0, 0x80, 0x80, // F
 ];
 
+/// The font in the COSMAC VIP's ROM, with a serifed 1, an open 4 and a straight 7.
+pub const VIP_FONT: [u8; 80] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xf0, 0x10, 0xf0, 0x80, 0xf0, // 2
+    0xf0, 0x10, 0xf0, 0x10, 0xf0, // 3
+    0xa0, 0xa0, 0xf0, 0x20, 0x20, // 4
+    0xf0, 0x80, 0xf0, 0x10, 0xf0, // 5
+    0xf0, 0x80, 0xf0, 0x90, 0xf0, // 6
+    0xf0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xf0, 0x90, 0xf0, 0x90, 0xf0, // 8
+    0xf0, 0x90, 0xf0, 0x10, 0xf0, // 9
+    0xf0, 0x90, 0xf0, 0x90, 0x90, // A
+    0xf0, 0x50, 0x70, 0x50, 0xf0, // B
+    0xf0, 0x80, 0x80, 0x80, 0xf0, // C
+    0xf0, 0x50, 0x50, 0x50, 0xf0, // D
+    0xf0, 0x80, 0xf0, 0x80, 0xf0, // E
+    0xf0, 0x80, 0xf0, 0x80, 0x80, // F
+];
+
 pub struct Memory {
-    memory: [u8; MEMORY_LENGTH],
+    memory: Vec<u8>,
+    font: &'static [u8; 80],
+    writes: Option<Vec<(u32, u8)>>,
 }
 
 impl Memory {
-    pub fn new() -> Self {
-        let memory = [0; MEMORY_LENGTH];
-        Self { memory }
+    /// `length` bytes of memory, `MEMORY_LENGTH` unless the platform has more, with `font` at the
+    /// start.
+    pub fn new(length: usize, font: &'static [u8; 80]) -> Self {
+        Self {
+            memory: vec![0; length],
+            font,
+            writes: None,
+        }
     }
 
-    pub fn read(&self, address: u16) -> u8 {
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn read(&self, address: u32) -> u8 {
         if address < 80 {
-            FONT[address as usize]
+            self.font[address as usize]
         } else if address < 0x200 {
             panic!("invalid memory access");
         } else {
@@ -38,7 +70,26 @@ impl Memory {
         }
     }
 
-    pub fn write(&mut self, address: u16, value: u8) {
+    /// Up to `length` bytes of program memory from `address`, cut short at the end of memory, or
+    /// `None` if `address` isn't in program memory.
+    pub fn get(&self, address: u32, length: usize) -> Option<&[u8]> {
+        let start = address as usize;
+        if start < 0x200 || start >= self.memory.len() {
+            return None;
+        }
+        let end = start.saturating_add(length).min(self.memory.len());
+        Some(&self.memory[start..end])
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) {
         self.memory[address as usize] = value;
+        if let Some(writes) = self.writes.as_mut() {
+            writes.push((address, value));
+        }
+    }
+
+    /// Returns the writes made since the last call, and keeps recording from then on.
+    pub fn take_writes(&mut self) -> Vec<(u32, u8)> {
+        self.writes.replace(Vec::new()).unwrap_or_default()
     }
 }
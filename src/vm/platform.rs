@@ -0,0 +1,73 @@
+use crate::vm::variant::{self, Variant};
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// The CHIP-8 variants that can be emulated, each described by a `Variant`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter.
+    #[default]
+    #[serde(rename = "chip-8")]
+    Chip8,
+    /// The ETI-660, which loads programs at 0x600 and has a 64x48 display.
+    #[serde(rename = "eti-660")]
+    Eti660,
+    /// The two-page "CHIP-8 hi-res" interpreter for the COSMAC VIP, with a 64x64 display. Its
+    /// programs are loaded at 0x200 but start at 0x2c0, past the patch to the interpreter.
+    #[serde(rename = "chip-8-hires")]
+    HiRes,
+    /// CHIP-8X, which loads programs at 0x300 and adds opcodes for the VP-590 colour board and a
+    /// second keypad.
+    #[serde(rename = "chip-8x")]
+    Chip8X,
+    /// MegaChip, whose mega mode has a 256x192 display in 256 colours and 24 bit addresses.
+    #[serde(rename = "megachip")]
+    MegaChip,
+}
+
+pub const PLATFORMS: [(&str, Platform); 5] = [
+    ("chip-8", Platform::Chip8),
+    ("eti-660", Platform::Eti660),
+    ("chip-8-hires", Platform::HiRes),
+    ("chip-8x", Platform::Chip8X),
+    ("megachip", Platform::MegaChip),
+];
+
+impl Platform {
+    /// What sets the platform apart.
+    pub fn variant(self) -> &'static dyn Variant {
+        match self {
+            Platform::Chip8 => &variant::Chip8,
+            Platform::Eti660 => &variant::Eti660,
+            Platform::HiRes => &variant::HiRes,
+            Platform::Chip8X => &variant::Chip8X,
+            Platform::MegaChip => &variant::MegaChip,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, _) = PLATFORMS
+            .iter()
+            .find(|(_, platform)| platform == self)
+            .expect("unnamed platform");
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PLATFORMS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, platform)| *platform)
+            .ok_or_else(|| {
+                let names: Vec<_> = PLATFORMS.iter().map(|(name, _)| *name).collect();
+                format!("expected one of {}: {:?}", names.join(", "), s)
+            })
+    }
+}
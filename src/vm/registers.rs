@@ -1,8 +1,10 @@
 use crate::opcode::DataRegister;
 
+#[derive(Clone)]
 pub struct Registers {
     registers: [u8; 16],
-    pub i: u16,
+    /// 24 bits wide for MegaChip, 16 bits otherwise.
+    pub i: u32,
 }
 
 impl Registers {
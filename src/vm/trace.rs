@@ -0,0 +1,167 @@
+use crate::opcode::DataRegister;
+use crate::vm::registers::Registers;
+use crate::vm::{State, Vm};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+/// The state of the VM after executing a single instruction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub pc: u16,
+    pub instruction: u16,
+    pub mnemonic: String,
+    /// Registers whose value changed, paired with their new value.
+    pub changed: Vec<(u8, u8)>,
+    pub i: u32,
+    pub dt: u8,
+    pub st: u8,
+    /// Memory writes made by the instruction as `(address, value)`.
+    #[serde(default)]
+    pub writes: Vec<(u32, u8)>,
+}
+
+impl Entry {
+    /// Compares everything but the mnemonic, which differs between disassemblers.
+    pub fn matches(&self, other: &Entry) -> bool {
+        self.pc == other.pc
+            && self.instruction == other.instruction
+            && self.changed == other.changed
+            && self.i == other.i
+            && self.dt == other.dt
+            && self.st == other.st
+            && self.writes == other.writes
+    }
+
+    /// One line of plain text with the mnemonic padded so the registers line up.
+    pub fn to_text(&self) -> String {
+        let mut line = format!(
+            "{:#05x} {:04x} {:<20}",
+            self.pc, self.instruction, self.mnemonic
+        );
+        for (register, value) in &self.changed {
+            line.push_str(&format!(
+                " {:?}={:02x}",
+                DataRegister::from(*register),
+                value
+            ));
+        }
+        line.push_str(&format!(
+            " I={:03x} DT={:02x} ST={:02x}",
+            self.i, self.dt, self.st
+        ));
+        for (address, value) in &self.writes {
+            line.push_str(&format!(" [{:03x}]={:02x}", address, value));
+        }
+        line
+    }
+}
+
+pub struct Tracer {
+    out: Box<dyn Write + Send>,
+    format: Format,
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write + Send>, format: Format) -> Self {
+        Self { out, format }
+    }
+
+    pub fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        match self.format {
+            Format::Text => writeln!(self.out, "{}", entry.to_text()),
+            Format::Json => {
+                serde_json::to_writer(&mut self.out, entry)?;
+                writeln!(self.out)
+            }
+        }
+    }
+}
+
+/// Reads a trace written in the JSON Lines format.
+pub fn read(reader: impl BufRead) -> io::Result<Vec<Entry>> {
+    reader
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// The first instruction at which a run differs from a reference trace.
+#[derive(Debug)]
+pub struct Divergence {
+    pub step: usize,
+    pub expected: Entry,
+    /// `None` if the VM stopped before reaching this step.
+    pub actual: Option<Entry>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "diverged at step {}", self.step)?;
+        writeln!(f, "expected: {}", self.expected.to_text())?;
+        match &self.actual {
+            Some(actual) => write!(f, "  actual: {}", actual.to_text()),
+            None => write!(f, "  actual: stopped"),
+        }
+    }
+}
+
+/// Steps `vm` once for every entry in `reference`, stopping at the first difference.
+pub fn diff(vm: &mut Vm, reference: &[Entry]) -> Result<(), Box<Divergence>> {
+    for (step, expected) in reference.iter().enumerate() {
+        let actual = match vm.step_traced() {
+            (State::Running, entry) => entry,
+            (_, _) => None,
+        };
+        if !actual
+            .as_ref()
+            .is_some_and(|actual| actual.matches(expected))
+        {
+            return Err(Box::new(Divergence {
+                step,
+                expected: expected.clone(),
+                actual,
+            }));
+        }
+    }
+    Ok(())
+}
+
+pub fn changed_registers(before: &Registers, after: &Registers) -> Vec<(u8, u8)> {
+    (0..16)
+        .map(DataRegister::from)
+        .filter(|r| before.read(*r) != after.read(*r))
+        .map(|r| (r as u8, after.read(r)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_text() {
+        let entry = Entry {
+            pc: 0x200,
+            instruction: 0x610a,
+            mnemonic: "LD V1, 0x0a".to_string(),
+            changed: vec![(1, 0x0a)],
+            i: 0,
+            dt: 0,
+            st: 0,
+            writes: vec![],
+        };
+
+        assert_eq!(
+            entry.to_text(),
+            "0x200 610a LD V1, 0x0a          V1=0a I=000 DT=00 ST=00"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! What sets each CHIP-8 variant apart, as implementations of `Variant`. Adding a variant, e.g.
+//! SCHIP or XO-CHIP, means implementing the trait, giving it a `Platform` and naming it in
+//! `PLATFORMS`.
+
+use crate::opcode::{self, DataRegister, DecodeError, Kk, Opcode, N};
+use crate::vm::cpu;
+use crate::vm::memory::{FONT, VIP_FONT};
+use crate::vm::{Blend, FrameBuffer, Quirks, Sample, Vm, HEIGHT, MEMORY_LENGTH, WIDTH};
+
+/// The memory, display, font, opcodes and quirks of a CHIP-8 variant. The defaults are CHIP-8
+/// as most interpreters run it.
+pub trait Variant: Sync {
+    /// Where programs are loaded.
+    fn load_address(&self) -> u16 {
+        0x200
+    }
+
+    /// How far past the load address programs start executing.
+    fn start_offset(&self) -> u16 {
+        0
+    }
+
+    /// The size of memory in bytes.
+    fn memory_length(&self) -> usize {
+        MEMORY_LENGTH
+    }
+
+    /// The width and height of the display in pixels.
+    fn display_size(&self) -> (usize, usize) {
+        (WIDTH, HEIGHT)
+    }
+
+    /// How many times wider than tall pixels are shown.
+    fn pixel_aspect(&self) -> usize {
+        1
+    }
+
+    /// A blank display, with any layers the variant draws in besides its pixels.
+    fn frame_buffer(&self) -> FrameBuffer {
+        let (width, height) = self.display_size();
+        FrameBuffer::new(width, height)
+    }
+
+    /// Decodes an instruction, including any opcodes the variant adds or changes.
+    fn decode(&self, instruction: u16) -> Result<Opcode, DecodeError> {
+        opcode::decode(instruction)
+    }
+
+    /// The hex digit sprites, 5 bytes each, at the start of memory.
+    fn font(&self) -> &'static [u8; 80] {
+        &FONT
+    }
+
+    /// Executes the opcodes the variant adds or changes, handing any others back to be executed
+    /// as CHIP-8 instructions.
+    fn execute(&self, _vm: &mut Vm, opcode: Opcode) -> Option<Opcode> {
+        Some(opcode)
+    }
+
+    /// The quirks programs for the variant expect, unless they're configured otherwise.
+    fn quirks(&self) -> Quirks {
+        Quirks::default()
+    }
+}
+
+/// The original COSMAC VIP interpreter.
+pub struct Chip8;
+
+impl Variant for Chip8 {}
+
+/// The ETI-660, which loads programs at 0x600 and has a 64x48 display.
+pub struct Eti660;
+
+impl Variant for Eti660 {
+    fn load_address(&self) -> u16 {
+        0x600
+    }
+
+    fn display_size(&self) -> (usize, usize) {
+        (64, 48)
+    }
+}
+
+/// The two-page "CHIP-8 hi-res" interpreter for the COSMAC VIP, with a 64x64 display and `0230`
+/// to clear it. Its programs are loaded at 0x200 but start at 0x2c0, past the patch to the
+/// interpreter.
+pub struct HiRes;
+
+impl Variant for HiRes {
+    fn start_offset(&self) -> u16 {
+        0xc0
+    }
+
+    fn display_size(&self) -> (usize, usize) {
+        (64, 64)
+    }
+
+    /// The display fits twice the rows in the same screen area.
+    fn pixel_aspect(&self) -> usize {
+        2
+    }
+
+    fn decode(&self, instruction: u16) -> Result<Opcode, DecodeError> {
+        opcode::decode_hires(instruction)
+    }
+
+    /// Its programs were only ever run on the COSMAC VIP, where `DRW` waits for the display.
+    fn quirks(&self) -> Quirks {
+        Quirks { display_wait: true }
+    }
+
+    /// The font in the VIP's ROM, which the interpreter patch leaves in place.
+    fn font(&self) -> &'static [u8; 80] {
+        &VIP_FONT
+    }
+
+    fn execute(&self, vm: &mut Vm, opcode: Opcode) -> Option<Opcode> {
+        match opcode {
+            Opcode::DisplayClearHiRes => {
+                vm.frame_buffer.clear();
+                cpu::advance(vm, 0);
+                None
+            }
+            _ => Some(opcode),
+        }
+    }
+}
+
+/// CHIP-8X, which loads programs at 0x300 and adds opcodes for the VP-590 colour board and a
+/// second keypad.
+pub struct Chip8X;
+
+impl Variant for Chip8X {
+    fn load_address(&self) -> u16 {
+        0x300
+    }
+
+    fn frame_buffer(&self) -> FrameBuffer {
+        let (width, height) = self.display_size();
+        FrameBuffer::new(width, height).with_colour_zones()
+    }
+
+    fn decode(&self, instruction: u16) -> Result<Opcode, DecodeError> {
+        opcode::decode_chip8x(instruction)
+    }
+
+    /// The VIP's ROM font.
+    fn font(&self) -> &'static [u8; 80] {
+        &VIP_FONT
+    }
+
+    /// Its programs were only ever run on the COSMAC VIP, where `DRW` waits for the display.
+    fn quirks(&self) -> Quirks {
+        Quirks { display_wait: true }
+    }
+
+    fn execute(&self, vm: &mut Vm, opcode: Opcode) -> Option<Opcode> {
+        let skipped = match opcode {
+            Opcode::CycleBackground => {
+                if let Some(zones) = vm.frame_buffer.colour_zones_mut() {
+                    zones.cycle_background();
+                }
+                0
+            }
+
+            // VX holds the first column of 8 pixel zones in its high nibble and how many more to
+            // colour in its low nibble. VX+1 holds the same for rows of 4 pixel tall zones when N
+            // is 0, or else the first of N pixel rows.
+            Opcode::COL(x, y, N(n)) => {
+                let horizontal = vm.registers.read(x) as usize;
+                let vertical = vm.registers.read(DataRegister::from(x as u8 + 1)) as usize;
+                let columns = (horizontal >> 4)..(horizontal >> 4) + (horizontal & 0xf) + 1;
+                let rows = match n {
+                    0 => (vertical >> 4) * 4..((vertical >> 4) + (vertical & 0xf) + 1) * 4,
+                    n => vertical..vertical + n as usize,
+                };
+                let colour = vm.registers.read(y);
+                if let Some(zones) = vm.frame_buffer.colour_zones_mut() {
+                    zones.set_foreground(columns, rows, colour);
+                }
+                0
+            }
+
+            Opcode::AddNibbles(x, y) => {
+                let sum = ((vm.registers.read(x) & 0x77) + (vm.registers.read(y) & 0x77)) & 0x77;
+                vm.registers.write(x, sum);
+                0
+            }
+
+            Opcode::SKP2(x) => {
+                (vm.try_key2().map(|key| key as u8) == Some(vm.registers.read(x))) as u16
+            }
+
+            Opcode::SKNP2(x) => {
+                (vm.try_key2().map(|key| key as u8) != Some(vm.registers.read(x))) as u16
+            }
+
+            _ => return Some(opcode),
+        };
+        cpu::advance(vm, skipped);
+        None
+    }
+}
+
+/// MegaChip, whose mega mode has a 256x192 display in 256 colours and 24 bit addresses.
+pub struct MegaChip;
+
+impl Variant for MegaChip {
+    fn memory_length(&self) -> usize {
+        0x100_0000
+    }
+
+    fn display_size(&self) -> (usize, usize) {
+        (256, 192)
+    }
+
+    fn frame_buffer(&self) -> FrameBuffer {
+        let (width, height) = self.display_size();
+        FrameBuffer::new(width, height).with_mega_display()
+    }
+
+    fn decode(&self, instruction: u16) -> Result<Opcode, DecodeError> {
+        opcode::decode_megachip(instruction)
+    }
+
+    fn execute(&self, vm: &mut Vm, opcode: Opcode) -> Option<Opcode> {
+        let mega_mode = vm
+            .frame_buffer
+            .mega_display()
+            .is_some_and(|mega_display| mega_display.enabled);
+
+        let skipped = match opcode {
+            // In mega mode clearing the screen also shows the frame that's been drawn
+            Opcode::DisplayClear if mega_mode => {
+                vm.frame_buffer.clear();
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.flip();
+                }
+                0
+            }
+
+            Opcode::DRW(x, y, _) if mega_mode => {
+                let (x, y) = (vm.registers.read(x), vm.registers.read(y));
+                let mega_display = vm.frame_buffer.mega_display().unwrap();
+                let length = mega_display.sprite_width * mega_display.sprite_height;
+                // Sprites are cut short at the end of memory
+                let sprite = vm.memory.get(vm.registers.i, length).unwrap_or_default();
+                let mega_display = vm.frame_buffer.mega_display_mut().unwrap();
+                let collision = mega_display.draw(x as usize, y as usize, sprite);
+                vm.registers.write(DataRegister::VF, collision as u8);
+                0
+            }
+
+            Opcode::MEGAOFF | Opcode::MEGAON => {
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.enabled = matches!(opcode, Opcode::MEGAON);
+                }
+                0
+            }
+
+            // The low 16 bits of the address are the next instruction
+            Opcode::LDHI(Kk(high)) => {
+                let low = (vm.memory.read(vm.pc as u32 + 2) as u32) << 8
+                    | vm.memory.read(vm.pc as u32 + 3) as u32;
+                vm.registers.i = (high as u32) << 16 | low;
+                1
+            }
+
+            // I is 24 bits wide
+            Opcode::AddI(x) => {
+                vm.registers.i = (vm.registers.i + vm.registers.read(x) as u32) & 0xff_ffff;
+                0
+            }
+
+            // Colours past the end of memory are left as they were
+            Opcode::LDPAL(Kk(count)) => {
+                let colours = vm.memory.get(vm.registers.i, count as usize * 4);
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    for (n, colour) in colours.unwrap_or_default().chunks_exact(4).enumerate() {
+                        mega_display.palette[n + 1] =
+                            u32::from_be_bytes([colour[0], colour[1], colour[2], colour[3]]);
+                    }
+                }
+                0
+            }
+
+            Opcode::SPRW(Kk(width)) => {
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.sprite_width = if width == 0 { 256 } else { width as usize };
+                }
+                0
+            }
+
+            Opcode::SPRH(Kk(height)) => {
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.sprite_height = if height == 0 { 256 } else { height as usize };
+                }
+                0
+            }
+
+            Opcode::ALPHA(Kk(alpha)) => {
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.alpha = alpha;
+                }
+                0
+            }
+
+            Opcode::DIGISND(N(n)) => {
+                vm.sample = Sample::read(&vm.memory, vm.registers.i, n == 0);
+                0
+            }
+
+            Opcode::STOPSND => {
+                vm.sample = None;
+                0
+            }
+
+            Opcode::BMODE(N(n)) => {
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.blend = Blend::from(n);
+                }
+                0
+            }
+
+            Opcode::CCOL(Kk(index)) => {
+                if let Some(mega_display) = vm.frame_buffer.mega_display_mut() {
+                    mega_display.collision_colour = index;
+                }
+                0
+            }
+
+            _ => return Some(opcode),
+        };
+        cpu::advance(vm, skipped);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opcode::{DataRegister, Opcode};
+    use crate::vm::{Platform, Vm, PLATFORMS};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_variants() {
+        for (name, platform) in PLATFORMS {
+            let variant = platform.variant();
+            let frame_buffer = variant.frame_buffer();
+            assert_eq!(
+                (frame_buffer.width(), frame_buffer.height()),
+                variant.display_size(),
+                "{}",
+                name
+            );
+            let start = (variant.load_address() + variant.start_offset()) as usize;
+            assert!(start < variant.memory_length(), "{}", name);
+        }
+        let hires = Platform::HiRes.variant();
+        assert_eq!(hires.decode(0x0230).unwrap().to_string(), "HCLS");
+        assert!(hires.quirks().display_wait);
+        assert!(!Platform::Chip8.variant().quirks().display_wait);
+
+        // The VIP's 1 starts with its flag
+        for (platform, row) in [(Platform::Chip8, 0x20), (Platform::Chip8X, 0x60)] {
+            let (_tx_key, rx_key) = mpsc::channel();
+            let mut vm = Vm::new(rx_key);
+            vm.set_platform(platform);
+            assert_eq!(vm.memory.read(5), row);
+        }
+    }
+
+    #[test]
+    fn test_execute() {
+        // 5XY1 only adds nibbles on CHIP-8X, where it's decoded, so CHIP-8 steps over it
+        for (platform, sum) in [(Platform::Chip8, 0x37), (Platform::Chip8X, 0x74)] {
+            let (_tx_key, rx_key) = mpsc::channel();
+            let mut vm = Vm::new(rx_key);
+            vm.set_platform(platform);
+            vm.registers.write(DataRegister::V0, 0x37);
+            vm.registers.write(DataRegister::V1, 0x45);
+            let pc = vm.pc;
+            let add = Opcode::AddNibbles(DataRegister::V0, DataRegister::V1);
+            vm.execute(add);
+            assert_eq!(vm.registers.read(DataRegister::V0), sum);
+            assert_eq!(vm.pc, pc + 2);
+        }
+
+        // I wraps at 16 bits, or 24 on MegaChip
+        for (platform, i) in [(Platform::Chip8, 0x0001), (Platform::MegaChip, 0x10001)] {
+            let (_tx_key, rx_key) = mpsc::channel();
+            let mut vm = Vm::new(rx_key);
+            vm.set_platform(platform);
+            vm.registers.i = 0xffff;
+            vm.registers.write(DataRegister::V0, 2);
+            vm.execute(Opcode::AddI(DataRegister::V0));
+            assert_eq!(vm.registers.i, i);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Runs the ROMs in `tests/roms` and compares every step against the snapshots in
+//! `tests/snapshots`, traces recorded from this emulator to catch changes in behaviour. There are
+//! no traces from a reference emulator to compare against yet.
+//!
+//! Set `UPDATE_SNAPSHOTS=1` to record them again after an intentional change.
+
+use chip_8::vm::trace::{self, Format, Tracer};
+use chip_8::vm::Vm;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, LineWriter};
+use std::sync::mpsc;
+
+const ROMS: [&str; 2] = ["arithmetic", "draw"];
+const CYCLES: usize = 150;
+
+fn vm(rom: &[u8]) -> Vm {
+    let (_tx_key, rx_key) = mpsc::channel();
+    let mut vm = Vm::new(rx_key);
+    vm.seed(0);
+    vm.load_rom(rom).unwrap();
+    vm
+}
+
+fn update(rom: &[u8], path: &str) {
+    let mut vm = vm(rom);
+    let file = File::create(path).expect("unable to create snapshot");
+    vm.trace(Tracer::new(Box::new(LineWriter::new(file)), Format::Json));
+    for _ in 0..CYCLES {
+        vm.step();
+    }
+}
+
+#[test]
+fn test_snapshots() {
+    for name in &ROMS {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let rom = fs::read(format!("{}/tests/roms/{}.ch8", dir, name)).expect("unable to read ROM");
+        let path = format!("{}/tests/snapshots/{}.jsonl", dir, name);
+
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            update(&rom, &path);
+        }
+
+        let file = File::open(&path).expect("unable to open snapshot");
+        let snapshot = trace::read(BufReader::new(file)).expect("unable to read snapshot");
+
+        if let Err(divergence) = trace::diff(&mut vm(&rom), &snapshot) {
+            panic!("{}: {}", name, divergence);
+        }
+    }
+}